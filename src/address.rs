@@ -0,0 +1,180 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::libnetplan::_netplan_address_iter_free;
+use crate::libnetplan::_netplan_address_iter_next;
+use crate::libnetplan::_netplan_netdef_new_address_iter;
+use crate::libnetplan::address_iter;
+use crate::libnetplan::copy_raw_string;
+use crate::libnetplan::NetplanNetDefinition;
+
+/// The preferred lifetime of an address. netplan only accepts "forever" or 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressLifetime {
+    Forever,
+    Zero,
+}
+
+impl FromStr for AddressLifetime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forever" => Ok(AddressLifetime::Forever),
+            "0" => Ok(AddressLifetime::Zero),
+            _ => Err(format!("invalid address lifetime '{s}'")),
+        }
+    }
+}
+
+/// A static address assigned to a netdef.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub address: IpAddr,
+    pub prefix_length: u8,
+    pub lifetime: Option<AddressLifetime>,
+    pub label: Option<String>,
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    /// Parses an address in the "address/prefix_length" form netplan uses.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_length) = s
+            .split_once('/')
+            .ok_or(format!("address '{s}' has no prefix length"))?;
+
+        let address =
+            IpAddr::from_str(address).map_err(|e| format!("invalid address '{s}': {e}"))?;
+        let prefix_length = u8::from_str(prefix_length)
+            .map_err(|e| format!("invalid prefix length in '{s}': {e}"))?;
+
+        Ok(Address {
+            address,
+            prefix_length,
+            lifetime: None,
+            label: None,
+        })
+    }
+}
+
+/* Wraps libnetplan's address iterator, which owns the NetplanAddressOptions
+   it returns and frees them when it moves forward, so everything is copied
+   before next() returns.
+*/
+pub(crate) struct AddressIter {
+    iter: *mut address_iter,
+}
+
+impl AddressIter {
+    pub(crate) fn new(netdef: *const NetplanNetDefinition) -> Self {
+        AddressIter {
+            iter: unsafe { _netplan_netdef_new_address_iter(netdef as *mut NetplanNetDefinition) },
+        }
+    }
+}
+
+impl Iterator for AddressIter {
+    type Item = Result<Address, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let options = unsafe { _netplan_address_iter_next(self.iter) };
+
+        if options.is_null() {
+            return None;
+        }
+
+        let (address, lifetime, label) = unsafe {
+            (
+                copy_raw_string((*options).address),
+                copy_raw_string((*options).lifetime),
+                copy_raw_string((*options).label),
+            )
+        };
+
+        let address = match address {
+            Some(address) => address,
+            None => return Some(Err("address is NULL".to_string())),
+        };
+
+        let lifetime = match lifetime.map(|l| AddressLifetime::from_str(&l)).transpose() {
+            Ok(lifetime) => lifetime,
+            Err(error) => return Some(Err(error)),
+        };
+
+        Some(Address::from_str(&address).map(|address| Address {
+            lifetime,
+            label,
+            ..address
+        }))
+    }
+}
+
+impl Drop for AddressIter {
+    fn drop(&mut self) {
+        unsafe { _netplan_address_iter_free(self.iter) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::state::State;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_address_from_str() {
+        let address = Address::from_str("2001:db8::1/64").unwrap();
+        assert_eq!(
+            address.address,
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))
+        );
+        assert_eq!(address.prefix_length, 64);
+
+        assert!(Address::from_str("192.168.0.1").is_err());
+        assert!(Address::from_str("192.168.0.300/24").is_err());
+    }
+
+    #[test]
+    fn test_netdef_addresses() {
+        let yaml = r#"
+network:
+  ethernets:
+    eth0:
+      addresses:
+        - 192.168.0.1/24
+        - "2001:db8::1/64"
+        - 10.0.0.1/8:
+            lifetime: 0
+            label: "eth0:1""#;
+
+        let mut parser = Parser::new();
+        parser.load_yaml_from_string(yaml).unwrap();
+        let state = State::try_from(parser).unwrap();
+
+        let netdef = state.iter().next().unwrap();
+        let addresses: Vec<Address> = netdef.addresses().map(Result::unwrap).collect();
+
+        assert_eq!(addresses.len(), 3);
+        assert!(addresses.contains(&Address {
+            address: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+            prefix_length: 24,
+            lifetime: None,
+            label: None,
+        }));
+        assert!(addresses.contains(&Address {
+            address: IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            prefix_length: 64,
+            lifetime: None,
+            label: None,
+        }));
+        assert!(addresses.contains(&Address {
+            address: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            prefix_length: 8,
+            lifetime: Some(AddressLifetime::Zero),
+            label: Some("eth0:1".to_string()),
+        }));
+    }
+}
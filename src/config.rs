@@ -0,0 +1,568 @@
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_yaml::Value;
+
+use crate::error::{NetplanError, NetplanResult};
+use crate::hierarchy::HierarchyLayer;
+use crate::keypath::{KeyPath, ToKeyPath};
+use crate::parser::{self, Parser};
+use crate::patch::{self, PatchOperation, PatchSet};
+use crate::state::State;
+
+/* Where `netplan set` writes keys that don't belong to any file yet */
+const FALLBACK_FILENAME: &str = "70-netplan-set.yaml";
+
+/// Changes to the configuration of a netplan hierarchy, as `netplan set`
+/// does.
+pub struct Config;
+
+impl Config {
+    /// Sets `key_path`, e.g. `ethernets.eth0.dhcp4` or
+    /// `vlans."eth0.100".id`, to the YAML `value` in the hierarchy at
+    /// `root_dir`. A "null" value deletes the key.
+    ///
+    /// The whole hierarchy is validated with the change applied before
+    /// anything is written. Only files in etc/netplan are written: the
+    /// change goes to `etc/netplan/<origin_hint>.yaml` if a hint is given,
+    /// otherwise to the file the netdef comes from if it is in etc/netplan,
+    /// or 70-netplan-set.yaml. Deleted keys are set to null there so they
+    /// don't come back from other files. Returns the files written.
+    pub fn set(
+        root_dir: impl AsRef<Path>,
+        key_path: impl ToKeyPath,
+        value: &str,
+        origin_hint: Option<&str>,
+    ) -> NetplanResult<Vec<PathBuf>> {
+        let mut patch_set = PatchSet::new();
+        patch_set.set(key_path, value)?;
+
+        Config::apply(root_dir, &patch_set, origin_hint)
+    }
+
+    /// Same as set(), for all the operations of `patch_set` at once. The
+    /// combined result is validated once, before anything is written.
+    /// Every file is staged in etc/netplan first, then renamed over its
+    /// original. If a rename fails, the files already replaced are restored;
+    /// a crash in between can still leave only some of them replaced.
+    ///
+    /// Changes to netdefs defined outside etc/netplan are written to
+    /// 70-netplan-set.yaml as overrides, which libnetplan must accept on
+    /// their own, e.g. a vlan override needs its id and link.
+    pub fn apply(
+        root_dir: impl AsRef<Path>,
+        patch_set: &PatchSet,
+        origin_hint: Option<&str>,
+    ) -> NetplanResult<Vec<PathBuf>> {
+        let root_dir = root_dir.as_ref();
+        let etc_dir = root_dir.join(HierarchyLayer::Etc.dir());
+
+        if let Some(hint) = origin_hint {
+            if hint.is_empty() || hint.contains('/') {
+                return Err(NetplanError::generic(&format!(
+                    "invalid origin hint '{hint}'"
+                )));
+            }
+        }
+
+        if patch_set.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let patch = patch_set.to_yaml()?;
+        let nullable = patch_set.to_nullable_yaml()?;
+
+        let mut parser = Parser::new();
+        parser.load_nullable_fields(&nullable)?;
+        parser.load_yaml_hierarchy(root_dir)?;
+        parser.load_yaml_from_string(&patch)?;
+        let mut state = State::new();
+        state.import_parser_state(parser)?;
+
+        /* Where the netdefs come from before the change, so a deleted netdef
+           still has an origin. If the hierarchy is only valid with the
+           change applied, the changed netdefs are looked up instead.
+        */
+        let mut parser = Parser::new();
+        parser.load_yaml_hierarchy(root_dir)?;
+        let mut origins = State::new();
+        if origins.import_parser_state(parser).is_err() {
+            origins = state;
+        }
+
+        let mut targets: Vec<(String, Vec<&PatchOperation>)> = Vec::new();
+
+        for operation in patch_set.operations() {
+            let target = match origin_hint {
+                Some(hint) => format!("{hint}.yaml"),
+                None => target_file(&etc_dir, &origins, operation)?,
+            };
+
+            match targets.iter_mut().find(|(name, _)| *name == target) {
+                Some((_, operations)) => operations.push(operation),
+                None => targets.push((target, vec![operation])),
+            }
+        }
+
+        targets.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let staging = Staging::new(&etc_dir)?;
+
+        let mut parser = Parser::new();
+        parser.load_nullable_fields(&nullable)?;
+        for (name, _) in &targets {
+            let file = etc_dir.join(name);
+            if file.is_file() {
+                let staged = staging.dir().join(name);
+                fs::copy(&file, &staged)
+                    .map_err(|e| NetplanError::io(&file.display().to_string(), &e))?;
+                parser.load_yaml(&staged)?;
+            }
+        }
+        parser.load_yaml_from_string(&patch)?;
+
+        let mut state = State::new();
+        state.import_parser_state(parser)?;
+
+        /* libnetplan writes each netdef back to the staged file it comes
+           from, and the rest to the default file
+        */
+        let staging_root = staging.root().to_str().ok_or_else(|| {
+            NetplanError::generic(&format!("invalid path '{}'", staging.root().display()))
+        })?;
+        match origin_hint {
+            Some(hint) => state.write_yaml_file(&format!("{hint}.yaml"), staging_root)?,
+            None => state.update_yaml_hierarchy(FALLBACK_FILENAME, staging_root)?,
+        }
+
+        let deleted_keys = patch_set.deleted_keys();
+        for (name, operations) in &targets {
+            for operation in operations {
+                if operation.is_unset() && deleted_keys.contains(&operation.key_path()) {
+                    write_null_override(&staging.dir().join(name), operation.key_path())?;
+                }
+            }
+        }
+
+        /* The files as they will be loaded, with the staged ones in place */
+        let mut parser = Parser::new();
+        for file in staged_hierarchy(root_dir, &staging, &targets)? {
+            parser.load_yaml(file)?;
+        }
+        State::new().import_parser_state(parser)?;
+
+        let names: Vec<&str> = targets.iter().map(|(name, _)| name.as_str()).collect();
+        commit(&etc_dir, &staging, &names)
+    }
+}
+
+/* Renames the staged `names` over the files of etc/netplan, then syncs the
+   directory so the renames are durable. The originals are hard linked into
+   the staging directory first: if a rename fails, the files already
+   replaced are put back, or removed if they didn't exist.
+*/
+fn commit(etc_dir: &Path, staging: &Staging, names: &[&str]) -> NetplanResult<Vec<PathBuf>> {
+    let backup_dir = staging.root().join("backup");
+    fs::create_dir(&backup_dir)
+        .map_err(|e| NetplanError::io(&backup_dir.display().to_string(), &e))?;
+
+    let mut staged_files: Vec<(PathBuf, PathBuf, Option<PathBuf>)> = Vec::new();
+
+    for name in names {
+        let staged = staging.dir().join(name);
+        if !staged.is_file() {
+            continue;
+        }
+
+        /* libnetplan doesn't sync what it writes */
+        File::open(&staged)
+            .and_then(|file| file.sync_all())
+            .map_err(|e| NetplanError::io(&staged.display().to_string(), &e))?;
+
+        let file = etc_dir.join(name);
+        let backup = match fs::hard_link(&file, backup_dir.join(name)) {
+            Ok(()) => Some(backup_dir.join(name)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(NetplanError::io(&file.display().to_string(), &e)),
+        };
+
+        staged_files.push((staged, file, backup));
+    }
+
+    for (index, (staged, file, _)) in staged_files.iter().enumerate() {
+        if let Err(e) = fs::rename(staged, file) {
+            for (_, file, backup) in &staged_files[..index] {
+                _ = match backup {
+                    Some(backup) => fs::rename(backup, file),
+                    None => fs::remove_file(file),
+                };
+            }
+            _ = sync_dir(etc_dir);
+
+            return Err(NetplanError::io(&file.display().to_string(), &e));
+        }
+    }
+
+    sync_dir(etc_dir).map_err(|e| NetplanError::io(&etc_dir.display().to_string(), &e))?;
+
+    Ok(staged_files.into_iter().map(|(_, file, _)| file).collect())
+}
+
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/* The name of the file in etc/netplan a change to `operation.key_path`
+   (network.<section>.<id>...) goes to: the file libnetplan says its netdef
+   comes from, if that is in etc/netplan, or the fallback file, which
+   overrides files in lib/netplan and run/netplan as they are never written.
+*/
+fn target_file(
+    etc_dir: &Path,
+    origins: &State,
+    operation: &PatchOperation,
+) -> NetplanResult<String> {
+    let filepath = match operation.key_path().segments().get(2) {
+        Some(id) => match origins.get_ref(id) {
+            Some(netdef) => netdef.filepath()?,
+            None => None,
+        },
+        None => None,
+    };
+
+    let filepath = match filepath {
+        Some(filepath) => PathBuf::from(filepath),
+        None => return Ok(FALLBACK_FILENAME.to_string()),
+    };
+
+    let in_etc = match (filepath.parent(), fs::canonicalize(etc_dir)) {
+        (Some(dir), Ok(etc_dir)) => fs::canonicalize(dir).is_ok_and(|dir| dir == etc_dir),
+        _ => false,
+    };
+
+    Ok(match filepath.file_name() {
+        Some(name) if in_etc => name.to_string_lossy().to_string(),
+        _ => FALLBACK_FILENAME.to_string(),
+    })
+}
+
+/* Sets `key_path` to null in the file at `path`, so the key doesn't come
+   back from other files of the hierarchy, as `netplan set` does.
+   libnetplan can't write nulls itself.
+*/
+fn write_null_override(path: &Path, key_path: &KeyPath) -> NetplanResult<()> {
+    let mut document: Value = match fs::read_to_string(path) {
+        Ok(yaml) => serde_yaml::from_str(&yaml)
+            .map_err(|e| NetplanError::generic(&format!("cannot parse {}: {e}", path.display())))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Value::Null,
+        Err(e) => return Err(NetplanError::io(&path.display().to_string(), &e)),
+    };
+
+    *patch::entry_mut(&mut document, key_path.segments()) = Value::Null;
+
+    let yaml = serde_yaml::to_string(&document)
+        .map_err(|e| NetplanError::generic(&format!("cannot serialize {}: {e}", path.display())))?;
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(yaml.as_bytes()))
+        .map_err(|e| NetplanError::io(&path.display().to_string(), &e))
+}
+
+/* The files of the hierarchy at `root_dir` in loading order, with the
+   staged targets in place of the files of the same name in etc/netplan.
+   Files in run/netplan still shadow them.
+*/
+fn staged_hierarchy(
+    root_dir: &Path,
+    staging: &Staging,
+    targets: &[(String, Vec<&PatchOperation>)],
+) -> NetplanResult<Vec<PathBuf>> {
+    let run_dir = root_dir.join(HierarchyLayer::Run.dir());
+    let mut files = parser::hierarchy_files(root_dir)
+        .map_err(|e| NetplanError::io(&root_dir.display().to_string(), &e))?;
+
+    for (name, _) in targets {
+        let staged = staging.dir().join(name);
+        if !staged.is_file() {
+            continue;
+        }
+
+        match files
+            .iter_mut()
+            .find(|file| file.file_name() == Some(name.as_ref()))
+        {
+            Some(file) if file.parent() == Some(run_dir.as_path()) => (),
+            Some(file) => *file = staged,
+            None => files.push(staged),
+        }
+    }
+
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+    Ok(files)
+}
+
+/* A directory in etc/netplan, so the staged files can be renamed over the
+   originals, laid out as a hierarchy root for libnetplan's writers. It is
+   removed with whatever is left in it when dropped.
+*/
+struct Staging {
+    root: PathBuf,
+    dir: PathBuf,
+}
+
+impl Staging {
+    fn new(etc_dir: &Path) -> NetplanResult<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let root = etc_dir.join(format!(
+            ".netplan-set.{}.{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let dir = root.join(HierarchyLayer::Etc.dir());
+
+        if root.exists() {
+            fs::remove_dir_all(&root)
+                .map_err(|e| NetplanError::io(&root.display().to_string(), &e))?;
+        }
+
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| NetplanError::io(&dir.display().to_string(), &e))?;
+
+        Ok(Staging { root, dir })
+    }
+
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::DirBuilder;
+    use tempfile::tempdir;
+
+    fn create_hierarchy(root_dir: &Path) {
+        for layer in HierarchyLayer::ALL {
+            DirBuilder::new()
+                .recursive(true)
+                .create(root_dir.join(layer.dir()))
+                .unwrap();
+        }
+
+        fs::write(
+            root_dir.join("lib/netplan/10-vendor.yaml"),
+            "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n",
+        )
+        .unwrap();
+        fs::write(
+            root_dir.join("etc/netplan/20-admin.yaml"),
+            "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n      mtu: 9000\n    eth1:\n      dhcp6: true\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_config_set() {
+        let root_dir = tempdir().unwrap();
+        create_hierarchy(root_dir.path());
+        let vendor = root_dir.path().join("lib/netplan/10-vendor.yaml");
+        let admin = root_dir.path().join("etc/netplan/20-admin.yaml");
+
+        let written = Config::set(root_dir.path(), "ethernets.eth1.dhcp4", "true", None).unwrap();
+        assert_eq!(written, vec![admin.clone()]);
+        assert_eq!(
+            fs::read_to_string(&vendor).unwrap(),
+            "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n"
+        );
+
+        let written = Config::set(
+            root_dir.path(),
+            "network.ethernets.eth0.dhcp4",
+            "null",
+            None,
+        )
+        .unwrap();
+        assert!(written.iter().all(|file| file.parent() == admin.parent()));
+        assert!(written
+            .iter()
+            .any(|file| fs::read_to_string(file).unwrap().contains("dhcp4: null")));
+        assert_eq!(
+            fs::read_to_string(&vendor).unwrap(),
+            "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n"
+        );
+
+        let written = Config::set(
+            root_dir.path(),
+            "ethernets.eth2.dhcp4",
+            "true",
+            Some("90-new"),
+        )
+        .unwrap();
+        let new_file = root_dir.path().join("etc/netplan/90-new.yaml");
+        assert_eq!(written, vec![new_file.clone()]);
+        assert_eq!(
+            fs::read_to_string(&new_file).unwrap(),
+            "network:\n  version: 2\n  ethernets:\n    eth2:\n      dhcp4: true\n"
+        );
+
+        let mut parser = Parser::new();
+        parser.load_yaml_hierarchy(root_dir.path()).unwrap();
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+        assert!(!state.get("eth0").unwrap().unwrap().dhcp4);
+        assert_eq!(
+            state.get_path_as::<u32>("ethernets.eth0.mtu").unwrap(),
+            9000
+        );
+        assert!(state.get("eth1").unwrap().unwrap().dhcp4);
+    }
+
+    #[test]
+    fn test_config_apply() {
+        let root_dir = tempdir().unwrap();
+        create_hierarchy(root_dir.path());
+        let vendor = root_dir.path().join("lib/netplan/10-vendor.yaml");
+        let admin = root_dir.path().join("etc/netplan/20-admin.yaml");
+
+        let mut patch_set = PatchSet::new();
+        patch_set
+            .set("ethernets.eth1.addresses", "[10.0.0.1/24]")
+            .unwrap()
+            .set("ethernets.eth1.routes", "[{to: default, via: 10.0.0.254}]")
+            .unwrap()
+            .set("ethernets.eth1.nameservers.addresses", "[10.0.0.254]")
+            .unwrap()
+            .set("ethernets.eth3.dhcp4", "true")
+            .unwrap();
+
+        let written = Config::apply(root_dir.path(), &patch_set, None).unwrap();
+        let fallback = root_dir.path().join("etc/netplan/70-netplan-set.yaml");
+        assert_eq!(written, vec![admin.clone(), fallback.clone()]);
+        assert!(fs::read_to_string(&admin).unwrap().contains("10.0.0.254"));
+        assert!(fs::read_to_string(&fallback).unwrap().contains("eth3"));
+        assert!(!fs::read_to_string(&vendor).unwrap().contains("eth1"));
+
+        /* The second route is invalid, nothing may be written */
+        let before = fs::read_to_string(&admin).unwrap();
+        let mut patch_set = PatchSet::new();
+        patch_set
+            .set("ethernets.eth1.mtu", "1400")
+            .unwrap()
+            .set("ethernets.eth1.routes", "[{to: default, via: notanip}]")
+            .unwrap();
+        assert!(Config::apply(root_dir.path(), &patch_set, None).is_err());
+        assert_eq!(fs::read_to_string(&admin).unwrap(), before);
+        assert_eq!(fs::read_dir(admin.parent().unwrap()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_config_apply_json_patch() {
+        let root_dir = tempdir().unwrap();
+        create_hierarchy(root_dir.path());
+
+        Config::set(
+            root_dir.path(),
+            "ethernets.eth1.addresses",
+            "[10.0.0.1/24, 10.0.0.2/24]",
+            None,
+        )
+        .unwrap();
+
+        let load_state = || {
+            let mut parser = Parser::new();
+            parser.load_yaml_hierarchy(root_dir.path()).unwrap();
+            let mut state = State::new();
+            state.import_parser_state(parser).unwrap();
+            state
+        };
+
+        let patch_set = PatchSet::from_json_patch(
+            r#"[{"op": "replace", "path": "/network/ethernets/eth1/addresses/1", "value": "10.0.0.3/24"}]"#,
+            &load_state(),
+        )
+        .unwrap();
+        Config::apply(root_dir.path(), &patch_set, None).unwrap();
+
+        /* The sequence is replaced, not appended to */
+        assert_eq!(
+            load_state()
+                .get_path_as::<Vec<String>>("ethernets.eth1.addresses")
+                .unwrap(),
+            vec!["10.0.0.1/24", "10.0.0.3/24"]
+        );
+    }
+
+    #[test]
+    fn test_config_set_invalid() {
+        let root_dir = tempdir().unwrap();
+        create_hierarchy(root_dir.path());
+        let admin = root_dir.path().join("etc/netplan/20-admin.yaml");
+        let before = fs::read_to_string(&admin).unwrap();
+
+        assert!(Config::set(root_dir.path(), "ethernets.eth1.dhcp4", "badvalue", None).is_err());
+        assert!(Config::set(root_dir.path(), "ethernets.eth1.dhcp4", "true", Some("")).is_err());
+        assert_eq!(fs::read_to_string(&admin).unwrap(), before);
+    }
+
+    #[test]
+    fn test_commit_rollback() {
+        let root_dir = tempdir().unwrap();
+        let etc_dir = root_dir.path().join("etc/netplan");
+        fs::create_dir_all(etc_dir.join("30-dir.yaml/inner")).unwrap();
+        fs::write(etc_dir.join("20-admin.yaml"), "old").unwrap();
+
+        let staging = Staging::new(&etc_dir).unwrap();
+        for name in ["10-new.yaml", "20-admin.yaml", "30-dir.yaml"] {
+            fs::write(staging.dir().join(name), "new").unwrap();
+        }
+
+        /* A file can't be renamed over a directory */
+        let names = ["10-new.yaml", "20-admin.yaml", "30-dir.yaml"];
+        assert!(commit(&etc_dir, &staging, &names).is_err());
+        drop(staging);
+
+        assert_eq!(
+            fs::read_to_string(etc_dir.join("20-admin.yaml")).unwrap(),
+            "old"
+        );
+        let mut entries: Vec<String> = fs::read_dir(&etc_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec!["20-admin.yaml", "30-dir.yaml"]);
+
+        let staging = Staging::new(&etc_dir).unwrap();
+        fs::write(staging.dir().join("20-admin.yaml"), "new").unwrap();
+        let written = commit(&etc_dir, &staging, &["10-new.yaml", "20-admin.yaml"]).unwrap();
+        assert_eq!(written, vec![etc_dir.join("20-admin.yaml")]);
+        assert_eq!(
+            fs::read_to_string(etc_dir.join("20-admin.yaml")).unwrap(),
+            "new"
+        );
+    }
+}
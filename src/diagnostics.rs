@@ -0,0 +1,257 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::error::NetplanError;
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_BOLD: &str = "\x1b[1m";
+const ANSI_RED: &str = "\x1b[1;31m";
+const ANSI_YELLOW: &str = "\x1b[1;33m";
+const ANSI_BLUE: &str = "\x1b[1;34m";
+const ANSI_CYAN: &str = "\x1b[1;36m";
+
+/* Prefix libnetplan puts in front of every parser error */
+const NETWORK_DEFINITION_ERROR: &str = "Error in network definition: ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Warning => ANSI_YELLOW,
+            Severity::Error => ANSI_RED,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A problem found in a configuration, with its location when known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    /// 1-based line number.
+    pub line: Option<usize>,
+    /// 1-based column number.
+    pub column: Option<usize>,
+    /// A suggestion on how to fix the problem.
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str) -> Self {
+        Diagnostic {
+            severity,
+            message: message.to_string(),
+            file: None,
+            line: None,
+            column: None,
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+
+    /// Renders the diagnostic like rustc does: a header, the location and,
+    /// if `source` holds the contents of the file, the offending line with
+    /// a caret under the column.
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{style}{text}{ANSI_RESET}")
+            } else {
+                text.to_string()
+            }
+        };
+
+        let source_line = match (source, self.line) {
+            (Some(source), Some(line)) if line > 0 => source.lines().nth(line - 1),
+            _ => None,
+        };
+        let gutter = " ".repeat(self.line.map_or(0, |line| line.to_string().len()));
+
+        let mut output = format!(
+            "{}{}\n",
+            paint(self.severity.color(), self.severity.name()),
+            paint(ANSI_BOLD, &format!(": {}", self.message))
+        );
+
+        if let Some(file) = &self.file {
+            let location = match (self.line, self.column) {
+                (Some(line), Some(column)) => format!("{}:{line}:{column}", file.display()),
+                (Some(line), None) => format!("{}:{line}", file.display()),
+                _ => file.display().to_string(),
+            };
+            output += &format!("{gutter}{} {location}\n", paint(ANSI_BLUE, "-->"));
+        }
+
+        if let (Some(source_line), Some(line)) = (source_line, self.line) {
+            let bar = paint(ANSI_BLUE, "|");
+
+            output += &format!("{gutter} {bar}\n");
+            output += &format!(
+                "{} {bar} {source_line}\n",
+                paint(ANSI_BLUE, &line.to_string())
+            );
+
+            if let Some(column) = self.column {
+                /* Keep the tabs so the caret lines up with the source */
+                let padding: String = source_line
+                    .chars()
+                    .take(column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+
+                output += &format!(
+                    "{gutter} {bar} {padding}{}\n",
+                    paint(self.severity.color(), "^")
+                );
+            }
+        }
+
+        if let Some(hint) = &self.hint {
+            output += &format!(
+                "{gutter} {} {}: {hint}\n",
+                paint(ANSI_BLUE, "="),
+                paint(ANSI_CYAN, "hint")
+            );
+        }
+
+        output
+    }
+
+    /// Same as render(), reading the source from `file`. The code frame is
+    /// left out if the file can't be read.
+    pub fn render_file(&self, color: bool) -> String {
+        let source = self
+            .file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok());
+
+        self.render(source.as_deref(), color)
+    }
+
+    /// A JSON object with the severity, message, location and hint, for
+    /// consumption by CI tooling. Unknown fields are null.
+    pub fn to_json(&self) -> String {
+        json!({
+            "severity": self.severity.name(),
+            "message": self.message,
+            "file": self.file.as_ref().map(|file| file.to_string_lossy()),
+            "line": self.line,
+            "column": self.column,
+            "hint": self.hint,
+        })
+        .to_string()
+    }
+}
+
+impl From<&NetplanError> for Diagnostic {
+    fn from(error: &NetplanError) -> Self {
+        let summary = error.summary();
+        let message = summary
+            .strip_prefix(NETWORK_DEFINITION_ERROR)
+            .unwrap_or(summary);
+
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.to_string(),
+            file: error.file.clone(),
+            line: error.line,
+            column: error.column,
+            hint: hint_for(message).map(|hint| hint.to_string()),
+        }
+    }
+}
+
+/* Hints for the parser errors users hit the most */
+fn hint_for(message: &str) -> Option<&'static str> {
+    if message.starts_with("invalid boolean value") {
+        Some("use true or false")
+    } else if message.contains("tabs are not allowed") {
+        Some("indent with spaces instead of tabs")
+    } else if message.starts_with("unknown key") {
+        Some("check the spelling of the key against the netplan reference")
+    } else if message.contains("is not a valid IP") || message.contains("invalid IP") {
+        Some("addresses are written as address/prefix_length, e.g. 192.168.0.1/24")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorCode, ErrorDomain};
+
+    fn create_error() -> NetplanError {
+        NetplanError::new(
+            ErrorDomain::Parser,
+            ErrorCode::InvalidConfig,
+            "/etc/netplan/10-config.yaml:5:14: Error in network definition: invalid boolean value 'badvalue'\n      dhcp4: badvalue\n             ^",
+        )
+    }
+
+    #[test]
+    fn test_diagnostic_render() {
+        let source =
+            "network:\n  ethernets:\n    eth0:\n      addresses: []\n      dhcp4: badvalue\n";
+        let diagnostic = Diagnostic::from(&create_error());
+
+        assert_eq!(diagnostic.message, "invalid boolean value 'badvalue'");
+        assert_eq!(
+            diagnostic.render(Some(source), false),
+            "error: invalid boolean value 'badvalue'\n \
+             --> /etc/netplan/10-config.yaml:5:14\n  \
+             |\n\
+             5 |       dhcp4: badvalue\n  \
+             |              ^\n  \
+             = hint: use true or false\n"
+        );
+
+        let colored = diagnostic.render(Some(source), true);
+        assert!(colored.starts_with("\x1b[1;31merror\x1b[0m"));
+
+        let without_source = diagnostic.render(None, false);
+        assert!(!without_source.contains("dhcp4: badvalue"));
+    }
+
+    #[test]
+    fn test_diagnostic_json() {
+        let diagnostic = Diagnostic::from(&create_error());
+        let json: serde_json::Value = serde_json::from_str(&diagnostic.to_json()).unwrap();
+
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["file"], "/etc/netplan/10-config.yaml");
+        assert_eq!(json["line"], 5);
+        assert_eq!(json["column"], 14);
+        assert_eq!(json["hint"], "use true or false");
+
+        let diagnostic = Diagnostic::new(Severity::Warning, "no location");
+        let json: serde_json::Value = serde_json::from_str(&diagnostic.to_json()).unwrap();
+        assert!(json["line"].is_null());
+    }
+}
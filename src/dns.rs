@@ -0,0 +1,214 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::error::NetplanResult;
+use crate::libnetplan::_netplan_nameserver_iter_free;
+use crate::libnetplan::_netplan_nameserver_iter_next;
+use crate::libnetplan::_netplan_netdef_new_nameserver_iter;
+use crate::libnetplan::_netplan_netdef_new_search_domain_iter;
+use crate::libnetplan::_netplan_search_domain_iter_free;
+use crate::libnetplan::_netplan_search_domain_iter_next;
+use crate::libnetplan::copy_raw_string;
+use crate::libnetplan::nameserver_iter;
+use crate::libnetplan::NetplanNetDefinition;
+use crate::netdef::NetdefRef;
+
+/// A resolver and the IDs of the netdefs configuring it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nameserver {
+    pub address: IpAddr,
+    pub netdefs: Vec<String>,
+}
+
+/// A search domain and the IDs of the netdefs configuring it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchDomain {
+    pub domain: String,
+    pub netdefs: Vec<String>,
+}
+
+/// The DNS settings of all the netdefs of a State, deduplicated and in the
+/// order they first appear.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dns {
+    pub nameservers: Vec<Nameserver>,
+    pub search_domains: Vec<SearchDomain>,
+}
+
+impl Dns {
+    pub(crate) fn from_netdefs<'a>(
+        netdefs: impl Iterator<Item = NetdefRef<'a>>,
+    ) -> NetplanResult<Self> {
+        let mut dns = Dns::default();
+
+        for netdef in netdefs {
+            let id = netdef.id()?;
+
+            for address in netdef.nameservers() {
+                let address = address?;
+                match dns.nameservers.iter_mut().find(|n| n.address == address) {
+                    Some(nameserver) => nameserver.netdefs.push(id.clone()),
+                    None => dns.nameservers.push(Nameserver {
+                        address,
+                        netdefs: vec![id.clone()],
+                    }),
+                }
+            }
+
+            for domain in netdef.search_domains() {
+                match dns.search_domains.iter_mut().find(|d| d.domain == domain) {
+                    Some(search_domain) => search_domain.netdefs.push(id.clone()),
+                    None => dns.search_domains.push(SearchDomain {
+                        domain,
+                        netdefs: vec![id.clone()],
+                    }),
+                }
+            }
+        }
+
+        Ok(dns)
+    }
+}
+
+pub(crate) struct NameserverIter {
+    iter: *mut nameserver_iter,
+}
+
+impl NameserverIter {
+    pub(crate) fn new(netdef: *const NetplanNetDefinition) -> Self {
+        NameserverIter {
+            iter: unsafe {
+                _netplan_netdef_new_nameserver_iter(netdef as *mut NetplanNetDefinition)
+            },
+        }
+    }
+}
+
+impl Iterator for NameserverIter {
+    type Item = Result<IpAddr, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let nameserver = copy_raw_string(unsafe { _netplan_nameserver_iter_next(self.iter) })?;
+
+        Some(
+            IpAddr::from_str(&nameserver)
+                .map_err(|e| format!("invalid nameserver '{nameserver}': {e}")),
+        )
+    }
+}
+
+impl Drop for NameserverIter {
+    fn drop(&mut self) {
+        unsafe { _netplan_nameserver_iter_free(self.iter) };
+    }
+}
+
+pub(crate) struct SearchDomainIter {
+    iter: *mut nameserver_iter,
+}
+
+impl SearchDomainIter {
+    pub(crate) fn new(netdef: *const NetplanNetDefinition) -> Self {
+        SearchDomainIter {
+            iter: unsafe {
+                _netplan_netdef_new_search_domain_iter(netdef as *mut NetplanNetDefinition)
+            },
+        }
+    }
+}
+
+impl Iterator for SearchDomainIter {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        copy_raw_string(unsafe { _netplan_search_domain_iter_next(self.iter) })
+    }
+}
+
+impl Drop for SearchDomainIter {
+    fn drop(&mut self) {
+        unsafe { _netplan_search_domain_iter_free(self.iter) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::state::State;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn create_state(yaml: &str) -> State {
+        let mut parser = Parser::new();
+        parser.load_yaml_from_string(yaml).unwrap();
+
+        State::try_from(parser).unwrap()
+    }
+
+    #[test]
+    fn test_netdef_nameservers() {
+        let yaml = r#"
+network:
+  ethernets:
+    eth0:
+      nameservers:
+        addresses:
+          - 192.168.0.254
+          - "2001:db8::53"
+        search:
+          - mydomain.local
+          - example.com"#;
+
+        let state = create_state(yaml);
+        let netdef = state.iter().next().unwrap();
+
+        let nameservers: Vec<IpAddr> = netdef.nameservers().map(Result::unwrap).collect();
+        assert_eq!(
+            nameservers,
+            vec![
+                IpAddr::V4(Ipv4Addr::new(192, 168, 0, 254)),
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)),
+            ]
+        );
+
+        let search_domains: Vec<String> = netdef.search_domains().collect();
+        assert_eq!(search_domains, vec!["mydomain.local", "example.com"]);
+    }
+
+    #[test]
+    fn test_state_dns() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      nameservers:
+        addresses: [192.168.0.254]
+        search: [mydomain.local]
+    eth1:
+      nameservers:
+        addresses: [192.168.0.254, 10.0.0.53]
+    eth2: {}";
+
+        let state = create_state(yaml);
+        let dns = state.dns().unwrap();
+
+        assert_eq!(dns.nameservers.len(), 2);
+
+        let shared = dns
+            .nameservers
+            .iter()
+            .find(|n| n.address == IpAddr::V4(Ipv4Addr::new(192, 168, 0, 254)))
+            .unwrap();
+        let mut shared_netdefs = shared.netdefs.clone();
+        shared_netdefs.sort();
+        assert_eq!(shared_netdefs, vec!["eth0", "eth1"]);
+
+        assert_eq!(
+            dns.search_domains,
+            vec![SearchDomain {
+                domain: "mydomain.local".to_string(),
+                netdefs: vec!["eth0".to_string()],
+            }]
+        );
+    }
+}
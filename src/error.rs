@@ -0,0 +1,258 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+
+use crate::libnetplan::GError;
+use crate::libnetplan::LibNetplanError;
+
+pub type NetplanResult<T> = result::Result<T, NetplanError>;
+
+/// The libnetplan error domain, i.e. the layer an error comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorDomain {
+    /// Errors raised by this crate rather than libnetplan.
+    Generic,
+    Parser,
+    Validation,
+    File,
+    Backend,
+    Emitter,
+    Format,
+}
+
+impl ErrorDomain {
+    fn from_raw(domain: u32) -> Self {
+        match domain {
+            1 => ErrorDomain::Parser,
+            2 => ErrorDomain::Validation,
+            3 => ErrorDomain::File,
+            4 => ErrorDomain::Backend,
+            5 => ErrorDomain::Emitter,
+            6 => ErrorDomain::Format,
+            _ => ErrorDomain::Generic,
+        }
+    }
+}
+
+impl fmt::Display for ErrorDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorDomain::Generic => "generic",
+            ErrorDomain::Parser => "parser",
+            ErrorDomain::Validation => "validation",
+            ErrorDomain::File => "file",
+            ErrorDomain::Backend => "backend",
+            ErrorDomain::Emitter => "emitter",
+            ErrorDomain::Format => "format",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The error code within its domain, as defined by libnetplan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The YAML document itself is malformed.
+    InvalidYaml,
+    /// The YAML is well formed but isn't a valid netplan configuration.
+    InvalidConfig,
+    ConfigGeneric,
+    ConfigValidation,
+    /// The errno of a failed file operation.
+    Errno(i32),
+    /// The backend doesn't support the configuration.
+    Unsupported,
+    BackendValidation,
+    YamlEmitter,
+    FormatInvalidYaml,
+    /// A code unknown to this crate, or an error raised by this crate.
+    Unknown(u32),
+}
+
+impl ErrorCode {
+    fn from_raw(domain: ErrorDomain, code: u32) -> Self {
+        match (domain, code) {
+            (ErrorDomain::Parser, 0) => ErrorCode::InvalidYaml,
+            (ErrorDomain::Parser, 1) => ErrorCode::InvalidConfig,
+            (ErrorDomain::Validation, 0) => ErrorCode::ConfigGeneric,
+            (ErrorDomain::Validation, 1) => ErrorCode::ConfigValidation,
+            (ErrorDomain::File, errno) => ErrorCode::Errno(errno as i32),
+            (ErrorDomain::Backend, 0) => ErrorCode::Unsupported,
+            (ErrorDomain::Backend, 1) => ErrorCode::BackendValidation,
+            (ErrorDomain::Emitter, 0) => ErrorCode::YamlEmitter,
+            (ErrorDomain::Format, 0) => ErrorCode::FormatInvalidYaml,
+            (_, code) => ErrorCode::Unknown(code),
+        }
+    }
+}
+
+/// An error reported by libnetplan or by this crate.
+///
+/// When the message starts with "file:line:column:", as parser errors do,
+/// the location is available in `file`, `line` and `column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetplanError {
+    pub domain: ErrorDomain,
+    pub code: ErrorCode,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    /// 1-based line number.
+    pub line: Option<usize>,
+    /// 1-based column number.
+    pub column: Option<usize>,
+}
+
+impl NetplanError {
+    pub fn new(domain: ErrorDomain, code: ErrorCode, message: &str) -> Self {
+        let (file, line, column) = match parse_location(message) {
+            Some((file, line, column)) => (Some(file), Some(line), Some(column)),
+            None => (None, None, None),
+        };
+
+        NetplanError {
+            domain,
+            code,
+            message: message.to_string(),
+            file,
+            line,
+            column,
+        }
+    }
+
+    pub(crate) fn from_libnetplan_error(error: &LibNetplanError) -> Self {
+        let domain = ErrorDomain::from_raw(error.domain);
+        let code = ErrorCode::from_raw(domain, error.code);
+
+        NetplanError::new(domain, code, &error.message)
+    }
+
+    /* The error set by a failed libnetplan call, which is freed, or a
+       generic one naming the call if libnetplan didn't set any.
+    */
+    pub(crate) fn from_raw_error(error: *mut GError, call: &str) -> Self {
+        match LibNetplanError::try_from_raw_error(error) {
+            Some(error) => NetplanError::from_libnetplan_error(&error),
+            None => NetplanError::generic(&format!("{call} failed")),
+        }
+    }
+
+    pub(crate) fn generic(message: &str) -> Self {
+        NetplanError::new(ErrorDomain::Generic, ErrorCode::Unknown(0), message)
+    }
+
+    pub(crate) fn validation(message: &str) -> Self {
+        NetplanError::new(
+            ErrorDomain::Validation,
+            ErrorCode::ConfigValidation,
+            message,
+        )
+    }
+
+    pub(crate) fn io(context: &str, error: &io::Error) -> Self {
+        NetplanError::new(
+            ErrorDomain::File,
+            ErrorCode::Errno(error.raw_os_error().unwrap_or(0)),
+            &format!("{context}: {error}"),
+        )
+    }
+
+    /// The first line of the message, without the location prefix and the
+    /// source context libnetplan appends after it.
+    pub fn summary(&self) -> &str {
+        let first_line = self.message.lines().next().unwrap_or_default();
+
+        match split_location(first_line) {
+            Some((_, _, _, rest)) => rest,
+            None => first_line,
+        }
+    }
+}
+
+impl fmt::Display for NetplanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for NetplanError {}
+
+fn parse_location(message: &str) -> Option<(PathBuf, usize, usize)> {
+    let (file, line, column, _) = split_location(message.lines().next()?)?;
+
+    Some((PathBuf::from(file), line, column))
+}
+
+/* Splits a "<file>:<line>:<column>: <rest>" line. The file may contain
+   colons itself, so every colon is tried in turn.
+*/
+fn split_location(line: &str) -> Option<(&str, usize, usize, &str)> {
+    for (index, _) in line.match_indices(':') {
+        if index == 0 {
+            continue;
+        }
+
+        let mut parts = line[index + 1..].splitn(3, ':');
+        let line_number = parts.next().and_then(|n| n.parse::<usize>().ok());
+        let column = parts.next().and_then(|n| n.parse::<usize>().ok());
+
+        if let (Some(line_number), Some(column), Some(rest)) = (line_number, column, parts.next()) {
+            return Some((&line[..index], line_number, column, rest.trim_start()));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_location() {
+        let error = NetplanError::new(
+            ErrorDomain::Parser,
+            ErrorCode::InvalidConfig,
+            "/etc/netplan/10-config.yaml:5:14: Error in network definition: invalid boolean value 'badvalue'\n      dhcp4: badvalue\n             ^",
+        );
+
+        assert_eq!(
+            error.file,
+            Some(PathBuf::from("/etc/netplan/10-config.yaml"))
+        );
+        assert_eq!(error.line, Some(5));
+        assert_eq!(error.column, Some(14));
+        assert_eq!(
+            error.summary(),
+            "Error in network definition: invalid boolean value 'badvalue'"
+        );
+
+        let error = NetplanError::validation("vrf0: VRF routes table mismatch (1000 != 2000)");
+        assert_eq!(error.file, None);
+        assert_eq!(error.line, None);
+        assert_eq!(
+            error.to_string(),
+            "vrf0: VRF routes table mismatch (1000 != 2000)"
+        );
+    }
+
+    #[test]
+    fn test_error_code_from_raw() {
+        assert_eq!(
+            ErrorCode::from_raw(ErrorDomain::from_raw(1), 0),
+            ErrorCode::InvalidYaml
+        );
+        assert_eq!(
+            ErrorCode::from_raw(ErrorDomain::from_raw(3), 2),
+            ErrorCode::Errno(2)
+        );
+        assert_eq!(
+            ErrorCode::from_raw(ErrorDomain::from_raw(42), 7),
+            ErrorCode::Unknown(7)
+        );
+
+        fn assert_send_sync<T: Error + Send + Sync + 'static>() {}
+        assert_send_sync::<NetplanError>();
+    }
+}
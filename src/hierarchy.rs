@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::error::{NetplanError, NetplanResult};
+
+/// A directory of the netplan hierarchy. Files in higher layers shadow the
+/// files with the same name in lower ones, i.e. Run > Etc > Lib.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HierarchyLayer {
+    Lib,
+    Etc,
+    Run,
+}
+
+impl HierarchyLayer {
+    /// Every layer, from lowest to highest precedence.
+    pub const ALL: [HierarchyLayer; 3] = [
+        HierarchyLayer::Lib,
+        HierarchyLayer::Etc,
+        HierarchyLayer::Run,
+    ];
+
+    /// The directory of the layer, relative to the root directory.
+    pub fn dir(&self) -> &'static str {
+        match self {
+            HierarchyLayer::Lib => "lib/netplan",
+            HierarchyLayer::Etc => "etc/netplan",
+            HierarchyLayer::Run => "run/netplan",
+        }
+    }
+}
+
+impl fmt::Display for HierarchyLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.dir())
+    }
+}
+
+/// A netplan hierarchy held in memory, mapping (layer, filename) to the YAML
+/// content of the file. It can be loaded with
+/// Parser::load_virtual_hierarchy() without touching the disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VirtualHierarchy {
+    files: BTreeMap<(HierarchyLayer, String), Vec<u8>>,
+}
+
+impl VirtualHierarchy {
+    pub fn new() -> Self {
+        VirtualHierarchy::default()
+    }
+
+    /// Builds a hierarchy from ((layer, filename), yaml) entries, see
+    /// insert().
+    pub fn from_files<K: AsRef<str>, V: Into<Vec<u8>>>(
+        files: impl IntoIterator<Item = ((HierarchyLayer, K), V)>,
+    ) -> NetplanResult<Self> {
+        let mut hierarchy = VirtualHierarchy::new();
+
+        for ((layer, filename), yaml) in files {
+            hierarchy.insert(layer, filename.as_ref(), yaml)?;
+        }
+
+        Ok(hierarchy)
+    }
+
+    /// Adds a file to `layer`, replacing the one with the same name if any.
+    /// As with a real hierarchy, only names ending in ".yaml" and not
+    /// starting with a dot are loaded, so other names, and names with a
+    /// '/', are rejected.
+    pub fn insert(
+        &mut self,
+        layer: HierarchyLayer,
+        filename: &str,
+        yaml: impl Into<Vec<u8>>,
+    ) -> NetplanResult<()> {
+        if !filename.ends_with(".yaml") || filename.starts_with('.') || filename.contains('/') {
+            return Err(NetplanError::generic(&format!(
+                "invalid file name '{filename}': expected a '*.yaml' name without '/' or a leading '.'"
+            )));
+        }
+
+        self.files
+            .insert((layer, filename.to_string()), yaml.into());
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, layer: HierarchyLayer, filename: &str) -> Option<Vec<u8>> {
+        self.files.remove(&(layer, filename.to_string()))
+    }
+
+    pub fn get(&self, layer: HierarchyLayer, filename: &str) -> Option<&[u8]> {
+        self.files
+            .get(&(layer, filename.to_string()))
+            .map(|yaml| yaml.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Every file, including the shadowed ones, by layer then name.
+    pub fn iter(&self) -> impl Iterator<Item = VirtualFile<'_>> {
+        self.files
+            .iter()
+            .map(|((layer, filename), yaml)| VirtualFile {
+                layer: *layer,
+                filename,
+                yaml,
+            })
+    }
+
+    /// The files netplan would load, in loading order: a file shadows the
+    /// files with the same name in lower layers, and the remaining files
+    /// are sorted by name.
+    pub fn files(&self) -> Vec<VirtualFile<'_>> {
+        let mut files: BTreeMap<&str, VirtualFile<'_>> = BTreeMap::new();
+
+        /* The map is ordered by layer first, so higher layers come last */
+        for ((layer, filename), yaml) in &self.files {
+            files.insert(
+                filename,
+                VirtualFile {
+                    layer: *layer,
+                    filename,
+                    yaml,
+                },
+            );
+        }
+
+        files.into_values().collect()
+    }
+}
+
+/// A file of a VirtualHierarchy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualFile<'a> {
+    pub layer: HierarchyLayer,
+    pub filename: &'a str,
+    pub yaml: &'a [u8],
+}
+
+impl VirtualFile<'_> {
+    /// Where the file would live relative to the root directory, e.g.
+    /// "etc/netplan/50-cloud-init.yaml".
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(self.layer.dir()).join(self.filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_hierarchy_files() {
+        let mut hierarchy = VirtualHierarchy::from_files([
+            ((HierarchyLayer::Run, "10-config.yaml"), "run"),
+            ((HierarchyLayer::Lib, "10-config.yaml"), "lib"),
+            ((HierarchyLayer::Etc, "10-config.yaml"), "etc"),
+            ((HierarchyLayer::Lib, "90-last.yaml"), "lib"),
+            ((HierarchyLayer::Etc, "01-first.yaml"), "etc"),
+        ])
+        .unwrap();
+
+        assert!(hierarchy
+            .insert(HierarchyLayer::Etc, "README", "not yaml")
+            .is_err());
+        assert!(hierarchy
+            .insert(HierarchyLayer::Etc, "conf.d/10-config.yaml", "nested")
+            .is_err());
+        assert!(hierarchy
+            .insert(HierarchyLayer::Etc, ".hidden.yaml", "hidden")
+            .is_err());
+        assert!(hierarchy
+            .insert(HierarchyLayer::Etc, ".yaml", "hidden")
+            .is_err());
+        assert_eq!(hierarchy.len(), 5);
+
+        let files = hierarchy.files();
+        let names: Vec<(HierarchyLayer, &str)> = files
+            .iter()
+            .map(|file| (file.layer, file.filename))
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                (HierarchyLayer::Etc, "01-first.yaml"),
+                (HierarchyLayer::Run, "10-config.yaml"),
+                (HierarchyLayer::Lib, "90-last.yaml"),
+            ]
+        );
+        assert_eq!(files[1].yaml, b"run");
+        assert_eq!(files[1].path(), PathBuf::from("run/netplan/10-config.yaml"));
+    }
+}
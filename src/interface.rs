@@ -0,0 +1,188 @@
+use crate::error::NetplanResult;
+use crate::netdef::NetdefRef;
+
+/// A network interface as seen on a host, used to evaluate `match` stanzas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub mac: Option<String>,
+    pub driver: Option<String>,
+}
+
+impl InterfaceInfo {
+    pub fn new(name: &str, mac: Option<&str>, driver: Option<&str>) -> Self {
+        InterfaceInfo {
+            name: name.to_string(),
+            mac: mac.map(|mac| mac.to_string()),
+            driver: driver.map(|driver| driver.to_string()),
+        }
+    }
+}
+
+/// An interface and the IDs of the netdefs claiming it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceMatch {
+    pub interface: InterfaceInfo,
+    pub netdefs: Vec<String>,
+}
+
+impl InterfaceMatch {
+    /// More than one netdef claims the interface.
+    pub fn is_ambiguous(&self) -> bool {
+        self.netdefs.len() > 1
+    }
+
+    pub fn is_unclaimed(&self) -> bool {
+        self.netdefs.is_empty()
+    }
+}
+
+/// The result of matching a list of interfaces against the physical
+/// netdefs (ethernets, wifis and modems) of a State.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceResolution {
+    pub interfaces: Vec<InterfaceMatch>,
+    /// Physical netdefs that don't match any of the interfaces.
+    pub unmatched_netdefs: Vec<String>,
+}
+
+impl InterfaceResolution {
+    pub(crate) fn from_netdefs<'a>(
+        netdefs: impl Iterator<Item = NetdefRef<'a>>,
+        interfaces: &[InterfaceInfo],
+    ) -> NetplanResult<Self> {
+        let physical: Vec<(String, NetdefRef<'a>)> = netdefs
+            .filter(|netdef| netdef.r#type().is_physical())
+            .map(|netdef| Ok((netdef.id()?, netdef)))
+            .collect::<NetplanResult<_>>()?;
+
+        let interfaces: Vec<InterfaceMatch> = interfaces
+            .iter()
+            .map(|interface| InterfaceMatch {
+                interface: interface.clone(),
+                netdefs: physical
+                    .iter()
+                    .filter(|(_, netdef)| netdef.matches(interface))
+                    .map(|(id, _)| id.clone())
+                    .collect(),
+            })
+            .collect();
+
+        let unmatched_netdefs = physical
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| !interfaces.iter().any(|i| i.netdefs.contains(id)))
+            .collect();
+
+        Ok(InterfaceResolution {
+            interfaces,
+            unmatched_netdefs,
+        })
+    }
+
+    pub fn ambiguous(&self) -> impl Iterator<Item = &InterfaceMatch> {
+        self.interfaces.iter().filter(|i| i.is_ambiguous())
+    }
+
+    pub fn unclaimed(&self) -> impl Iterator<Item = &InterfaceMatch> {
+        self.interfaces.iter().filter(|i| i.is_unclaimed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::state::State;
+
+    fn create_state(yaml: &str) -> State {
+        let mut parser = Parser::new();
+        parser.load_yaml_from_string(yaml).unwrap();
+
+        State::try_from(parser).unwrap()
+    }
+
+    #[test]
+    fn test_netdef_matches() {
+        let yaml = r"
+network:
+  ethernets:
+    lan:
+      match:
+        macaddress: aa:bb:cc:dd:ee:ff
+    eth1: {}";
+
+        let state = create_state(yaml);
+        let by_mac = InterfaceInfo::new("enp1s0", Some("aa:bb:cc:dd:ee:ff"), None);
+        let by_name = InterfaceInfo::new("eth1", None, None);
+
+        for netdef in state.ethernets() {
+            if netdef.id().unwrap() == "lan" {
+                assert!(netdef.matches(&by_mac));
+                assert!(!netdef.matches(&by_name));
+            } else {
+                assert!(!netdef.matches(&by_mac));
+                assert!(netdef.matches(&by_name));
+            }
+        }
+    }
+
+    #[test]
+    fn test_netdef_ref_matches() {
+        let yaml = r#"
+network:
+  ethernets:
+    lan:
+      match:
+        name: "enp*"
+        driver: e1000"#;
+
+        let state = create_state(yaml);
+        let netdef = state.get_ref("lan").unwrap();
+
+        assert!(netdef.matches(&InterfaceInfo::new("enp1s0", None, Some("e1000"))));
+        assert!(!netdef.matches(&InterfaceInfo::new("enp1s0", None, Some("igb"))));
+        assert!(!netdef.matches(&InterfaceInfo::new("eth0", None, Some("e1000"))));
+    }
+
+    #[test]
+    fn test_state_resolve_interfaces() {
+        let yaml = r#"
+network:
+  ethernets:
+    virtio:
+      match:
+        driver: virtio_net
+    lan:
+      match:
+        name: "enp*"
+    wan:
+      match:
+        macaddress: 00:11:22:33:44:55
+  bonds:
+    bond0:
+      interfaces: [wan]"#;
+
+        let state = create_state(yaml);
+        let interfaces = vec![
+            InterfaceInfo::new("enp1s0", Some("aa:bb:cc:dd:ee:ff"), Some("virtio_net")),
+            InterfaceInfo::new("eth9", Some("aa:bb:cc:dd:ee:00"), Some("e1000")),
+        ];
+
+        let resolution = state.resolve_interfaces(&interfaces).unwrap();
+
+        let mut claimed = resolution.interfaces[0].netdefs.clone();
+        claimed.sort();
+        assert_eq!(claimed, vec!["lan", "virtio"]);
+
+        let ambiguous: Vec<&InterfaceMatch> = resolution.ambiguous().collect();
+        assert_eq!(ambiguous.len(), 1);
+        assert_eq!(ambiguous[0].interface.name, "enp1s0");
+
+        let unclaimed: Vec<&InterfaceMatch> = resolution.unclaimed().collect();
+        assert_eq!(unclaimed.len(), 1);
+        assert_eq!(unclaimed[0].interface.name, "eth9");
+
+        assert_eq!(resolution.unmatched_netdefs, vec!["wan"]);
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{NetplanError, NetplanResult};
+
+/* The separator libnetplan expects between the segments of a path */
+const NETPLAN_PATH_SEPARATOR: char = '\t';
+
+/// A path to a node of a netplan configuration, e.g.
+/// `network.ethernets.eth0.mtu`.
+///
+/// In its string form, segments containing dots or quotes are written
+/// between double quotes, with `"` and `\` escaped by a backslash, e.g.
+/// `network.vlans."eth0.100".id`. Paths built from segments need no quoting.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct KeyPath {
+    segments: Vec<String>,
+}
+
+impl KeyPath {
+    pub fn new() -> Self {
+        KeyPath::default()
+    }
+
+    pub fn from_segments<S: Into<String>>(segments: impl IntoIterator<Item = S>) -> Self {
+        KeyPath {
+            segments: segments.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Parses the string form, see KeyPath.
+    pub fn parse(key_path: &str) -> NetplanResult<Self> {
+        let invalid = |reason: &str| {
+            NetplanError::generic(&format!("invalid key path '{key_path}': {reason}"))
+        };
+
+        let mut segments = Vec::new();
+        let mut chars = key_path.chars().peekable();
+
+        loop {
+            let mut segment = String::new();
+
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => segment.push(c),
+                            None => return Err(invalid("unterminated quote")),
+                        },
+                        Some(c) => segment.push(c),
+                        None => return Err(invalid("unterminated quote")),
+                    }
+                }
+
+                if !matches!(chars.peek(), None | Some('.')) {
+                    return Err(invalid("expected '.' after a quoted segment"));
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != '.') {
+                    if c == '"' {
+                        return Err(invalid("unexpected quote"));
+                    }
+                    segment.push(c);
+                }
+
+                if segment.is_empty() {
+                    return Err(invalid("empty segment"));
+                }
+            }
+
+            segments.push(segment);
+
+            if chars.next().is_none() {
+                break;
+            }
+        }
+
+        Ok(KeyPath { segments })
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn push(&mut self, segment: impl Into<String>) {
+        self.segments.push(segment.into());
+    }
+
+    /// A new path with `segment` appended.
+    pub fn join(&self, segment: impl Into<String>) -> Self {
+        let mut key_path = self.clone();
+        key_path.push(segment);
+        key_path
+    }
+
+    /// The path under the top-level "network" key, which may be omitted.
+    pub(crate) fn rooted(&self) -> Self {
+        match self.segments.first() {
+            Some(first) if first == "network" => self.clone(),
+            _ => {
+                let mut key_path = KeyPath::from_segments(["network"]);
+                key_path.segments.extend(self.segments.iter().cloned());
+                key_path
+            }
+        }
+    }
+
+    /* The form libnetplan's utils take, which can't represent tabs */
+    pub(crate) fn to_netplan_path(&self) -> NetplanResult<String> {
+        if self
+            .segments
+            .iter()
+            .any(|segment| segment.contains(NETPLAN_PATH_SEPARATOR))
+        {
+            return Err(NetplanError::generic(&format!(
+                "invalid key path '{self}': segments can't contain tabs"
+            )));
+        }
+
+        Ok(self.segments.join(&NETPLAN_PATH_SEPARATOR.to_string()))
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, segment) in self.segments.iter().enumerate() {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+
+            if segment.is_empty() || segment.contains(['.', '"', '\\']) {
+                let escaped = segment.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{escaped}\"")?;
+            } else {
+                write!(f, "{segment}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for KeyPath {
+    type Err = NetplanError;
+
+    fn from_str(key_path: &str) -> Result<Self, Self::Err> {
+        KeyPath::parse(key_path)
+    }
+}
+
+/// Anything the path-taking APIs accept: a KeyPath, or its string form.
+pub trait ToKeyPath {
+    fn to_key_path(&self) -> NetplanResult<KeyPath>;
+}
+
+impl ToKeyPath for KeyPath {
+    fn to_key_path(&self) -> NetplanResult<KeyPath> {
+        Ok(self.clone())
+    }
+}
+
+impl ToKeyPath for str {
+    fn to_key_path(&self) -> NetplanResult<KeyPath> {
+        KeyPath::parse(self)
+    }
+}
+
+impl ToKeyPath for String {
+    fn to_key_path(&self) -> NetplanResult<KeyPath> {
+        KeyPath::parse(self)
+    }
+}
+
+impl<T: ToKeyPath + ?Sized> ToKeyPath for &T {
+    fn to_key_path(&self) -> NetplanResult<KeyPath> {
+        (**self).to_key_path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_path_parse() {
+        let key_path = KeyPath::parse(r#"network.vlans."eth0.100".id"#).unwrap();
+        assert_eq!(key_path.segments(), ["network", "vlans", "eth0.100", "id"]);
+        assert_eq!(key_path.to_string(), r#"network.vlans."eth0.100".id"#);
+
+        let key_path = KeyPath::parse(r#"wifis.wlan0.access-points."my \"ssid\" \\ 2.4""#).unwrap();
+        assert_eq!(key_path.segments()[3], r#"my "ssid" \ 2.4"#);
+        assert_eq!(KeyPath::parse(&key_path.to_string()).unwrap(), key_path);
+
+        for invalid in [
+            "",
+            "network..eth0",
+            "network.",
+            r#""eth0"x"#,
+            r#"a."b"#,
+            r#"a"b"#,
+        ] {
+            assert!(KeyPath::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_key_path_segments() {
+        let key_path = KeyPath::from_segments(["vlans", "eth0.100"]).join("id");
+        assert_eq!(key_path.to_string(), r#"vlans."eth0.100".id"#);
+        assert_eq!(
+            key_path.rooted().to_netplan_path().unwrap(),
+            "network\tvlans\teth0.100\tid"
+        );
+        assert_eq!(key_path.rooted().rooted(), key_path.rooted());
+        assert!(KeyPath::from_segments(["a\tb"]).to_netplan_path().is_err());
+        assert_eq!(
+            "a.b".to_key_path().unwrap(),
+            KeyPath::from_segments(["a", "b"])
+        );
+    }
+}
@@ -1,5 +1,20 @@
-pub mod libnetplan;
+pub mod address;
+pub mod config;
+pub mod diagnostics;
+pub mod dns;
+pub mod error;
+pub mod hierarchy;
+pub mod interface;
+pub mod keypath;
+pub(crate) mod libnetplan;
 pub mod netdef;
 pub mod parser;
+pub mod patch;
+pub mod provenance;
+pub mod route;
+pub mod sriov;
 pub mod state;
+pub mod topology;
 pub mod utils;
+pub mod validate;
+pub mod value;
@@ -2,34 +2,23 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(improper_ctypes)]
+#![allow(dead_code)]
 
 use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
 use std::os::fd::{FromRawFd, OwnedFd};
-use std::result;
+use std::os::raw::c_char;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 
+use crate::error::NetplanResult;
+use crate::netdef::Backend;
 use crate::netdef::NetdefType;
+use crate::topology::LinkType;
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-#[derive(Debug)]
-pub enum NetplanErrorDomains {
-    NetplanGenericError,
-    NetplanParserError(String),
-    NetplanValidationError(String),
-    NetplanFileError(String),
-}
-
-impl NetplanErrorDomains {
-    pub(crate) fn from_libnetplan_error(error: &LibNetplanError) -> Self {
-        match error.domain {
-            1 => NetplanErrorDomains::NetplanParserError(error.message.clone()),
-            2 => NetplanErrorDomains::NetplanValidationError(error.message.clone()),
-            3 => NetplanErrorDomains::NetplanFileError(error.message.clone()),
-            _ => NetplanErrorDomains::NetplanGenericError,
-        }
-    }
-}
-
 pub(crate) struct LibNetplanError {
     pub(crate) code: u32,
     pub(crate) domain: u32,
@@ -38,8 +27,19 @@ pub(crate) struct LibNetplanError {
 
 impl LibNetplanError {
     pub fn try_from_raw_error(error: *mut NetplanError) -> Option<Self> {
-        let message = error_get_message(error)?;
-        let error_code = error_get_code(error)?;
+        if error.is_null() {
+            return None;
+        }
+
+        let message = error_get_message(error);
+        let error_code = error_get_code(error);
+
+        /* The caller hands the error over, so it's freed once copied */
+        let mut error = error;
+        unsafe { netplan_error_clear(&mut error) };
+
+        let message = message?;
+        let error_code = error_code?;
 
         let domain = (error_code >> 32) as u32;
         let code = error_code as u32;
@@ -52,19 +52,140 @@ impl LibNetplanError {
     }
 }
 
-pub type NetplanResult<T> = result::Result<T, NetplanErrorDomains>;
-
 pub(crate) fn netdef_get_id(netdef: *const NetplanNetDefinition) -> Result<String, String> {
-    let name_string = unsafe {
+    unsafe {
         copy_string_realloc_call(
             |netdef, buffer, len| {
                 netplan_netdef_get_id(netdef as *const netplan_net_definition, buffer, len)
             },
             netdef as *const i8,
         )
-        .unwrap()
-    };
-    Ok(name_string)
+    }
+}
+
+pub(crate) fn netdef_get_filepath(
+    netdef: *const NetplanNetDefinition,
+) -> Result<Option<String>, String> {
+    unsafe {
+        copy_optional_string_realloc_call(
+            |netdef, buffer, len| {
+                netplan_netdef_get_filepath(netdef as *const netplan_net_definition, buffer, len)
+            },
+            netdef as *const i8,
+        )
+    }
+}
+
+pub(crate) fn netdef_get_set_name(
+    netdef: *const NetplanNetDefinition,
+) -> Result<Option<String>, String> {
+    unsafe {
+        copy_optional_string_realloc_call(
+            |netdef, buffer, len| {
+                netplan_netdef_get_set_name(netdef as *const netplan_net_definition, buffer, len)
+            },
+            netdef as *const i8,
+        )
+    }
+}
+
+pub(crate) fn netdef_get_macaddress(
+    netdef: *const NetplanNetDefinition,
+) -> Result<Option<String>, String> {
+    unsafe {
+        copy_optional_string_realloc_call(
+            |netdef, buffer, len| {
+                netplan_netdef_get_macaddress(netdef as *const netplan_net_definition, buffer, len)
+            },
+            netdef as *const i8,
+        )
+    }
+}
+
+pub(crate) fn netdef_get_dhcp4(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { netplan_netdef_get_dhcp4(netdef) != 0 }
+}
+
+pub(crate) fn netdef_get_dhcp6(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { netplan_netdef_get_dhcp6(netdef) != 0 }
+}
+
+pub(crate) fn netdef_get_link_local_ipv4(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { netplan_netdef_get_link_local_ipv4(netdef) != 0 }
+}
+
+pub(crate) fn netdef_get_link_local_ipv6(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { netplan_netdef_get_link_local_ipv6(netdef) != 0 }
+}
+
+pub(crate) fn netdef_has_match(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { netplan_netdef_has_match(netdef) != 0 }
+}
+
+pub(crate) fn netdef_get_critical(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { _netplan_netdef_get_critical(netdef) != 0 }
+}
+
+pub(crate) fn netdef_get_embedded_switch_mode(
+    netdef: *const NetplanNetDefinition,
+) -> Result<Option<String>, String> {
+    unsafe {
+        copy_optional_string_realloc_call(
+            |netdef, buffer, len| {
+                _netplan_netdef_get_embedded_switch_mode(
+                    netdef as *const netplan_net_definition,
+                    buffer,
+                    len,
+                )
+            },
+            netdef as *const i8,
+        )
+    }
+}
+
+pub(crate) fn netdef_get_sriov_vlan_filter(netdef: *const NetplanNetDefinition) -> bool {
+    unsafe { _netplan_netdef_get_sriov_vlan_filter(netdef) != 0 }
+}
+
+pub(crate) fn netdef_get_delay_virtual_functions_rebind(
+    netdef: *const NetplanNetDefinition,
+) -> bool {
+    unsafe { _netplan_netdef_get_delay_virtual_functions_rebind(netdef) != 0 }
+}
+
+/* The number of VFs is either the explicit virtual-function-count or the
+   number of netdefs linking to the PF. It fails if more VFs link to the PF
+   than the explicit count allows.
+*/
+pub(crate) fn state_get_vf_count_for_def(
+    state: *const NetplanState,
+    netdef: *const NetplanNetDefinition,
+) -> NetplanResult<u32> {
+    let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+
+    let count = unsafe { _netplan_state_get_vf_count_for_def(state, netdef, &mut netplan_error) };
+
+    if count < 0 {
+        if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
+            return Err(crate::error::NetplanError::from_libnetplan_error(&error));
+        }
+        return Err(crate::error::NetplanError::validation(
+            "failed to count the VFs of the PF",
+        ));
+    }
+
+    Ok(count as u32)
+}
+
+/* libnetplan initializes vlan_id to G_MAXUINT when it's not set */
+pub(crate) fn netdef_get_vlan_id(netdef: *const NetplanNetDefinition) -> Option<u32> {
+    let vlan_id = unsafe { _netplan_netdef_get_vlan_id(netdef) };
+
+    if vlan_id == u32::MAX {
+        None
+    } else {
+        Some(vlan_id)
+    }
 }
 
 pub(crate) fn error_get_message(error: *mut GError) -> Option<String> {
@@ -86,6 +207,19 @@ pub(crate) fn error_get_code(error: *mut GError) -> Option<u64> {
 }
 
 fn copy_string_realloc_call<F>(call: F, ptr: *const i8) -> Result<String, String>
+where
+    F: FnOnce(*const i8, *mut i8, usize) -> isize + Copy,
+{
+    match copy_optional_string_realloc_call(call, ptr)? {
+        Some(string) => Ok(string),
+        None => Err("copied is zero".to_string()),
+    }
+}
+
+/* libnetplan returns 0 from its string getters when the underlying
+   field is NULL, so callers interested in optional fields get None.
+*/
+fn copy_optional_string_realloc_call<F>(call: F, ptr: *const i8) -> Result<Option<String>, String>
 where
     F: FnOnce(*const i8, *mut i8, usize) -> isize + Copy,
 {
@@ -95,7 +229,7 @@ where
         let copied = call(ptr, name.as_mut_ptr() as *mut i8, name.len());
 
         if copied == 0 {
-            return Err("copied is zero".to_string());
+            return Ok(None);
         }
 
         if copied == -2 {
@@ -107,11 +241,54 @@ where
             continue;
         }
 
-        let name_raw = CStr::from_bytes_until_nul(&name).unwrap();
+        let name_raw = CStr::from_bytes_until_nul(&name).map_err(|e| e.to_string())?;
         let name_string = name_raw.to_string_lossy().to_string();
 
-        return Ok(name_string);
+        return Ok(Some(name_string));
+    }
+}
+
+pub(crate) fn netdef_match_interface(
+    netdef: *const NetplanNetDefinition,
+    name: &str,
+    mac: Option<&str>,
+    driver: Option<&str>,
+) -> bool {
+    /* Strings with interior NUL bytes can't name a real interface */
+    let Ok(name) = CString::new(name) else {
+        return false;
+    };
+    let Ok(mac) = mac.map(CString::new).transpose() else {
+        return false;
+    };
+    let Ok(driver) = driver.map(CString::new).transpose() else {
+        return false;
+    };
+
+    let ret = unsafe {
+        netplan_netdef_match_interface(
+            netdef,
+            name.as_ptr(),
+            mac.as_ref().map_or(::std::ptr::null(), |mac| mac.as_ptr()),
+            driver
+                .as_ref()
+                .map_or(::std::ptr::null(), |driver| driver.as_ptr()),
+        )
+    };
+
+    ret != 0
+}
+
+/* Copies a string owned by libnetplan, such as the fields returned by its
+   iterators, which are only valid until the iterator moves forward.
+*/
+pub(crate) fn copy_raw_string(ptr: *const i8) -> Option<String> {
+    if ptr.is_null() {
+        return None;
     }
+
+    let string = unsafe { CStr::from_ptr(ptr) };
+    Some(string.to_string_lossy().to_string())
 }
 
 pub(crate) fn netdef_get_type(netdef: *const NetplanNetDefinition) -> NetdefType {
@@ -134,18 +311,130 @@ pub(crate) fn netdef_get_type(netdef: *const NetplanNetDefinition) -> NetdefType
     }
 }
 
+pub(crate) fn netdef_type_to_raw(netdef_type: NetdefType) -> NetplanDefType {
+    match netdef_type {
+        NetdefType::None => NetplanDefType_NETPLAN_DEF_TYPE_NONE,
+        NetdefType::Ethernet => NetplanDefType_NETPLAN_DEF_TYPE_ETHERNET,
+        NetdefType::Wifi => NetplanDefType_NETPLAN_DEF_TYPE_WIFI,
+        NetdefType::Modem => NetplanDefType_NETPLAN_DEF_TYPE_MODEM,
+        NetdefType::Bridge => NetplanDefType_NETPLAN_DEF_TYPE_BRIDGE,
+        NetdefType::Bond => NetplanDefType_NETPLAN_DEF_TYPE_BOND,
+        NetdefType::Vlan => NetplanDefType_NETPLAN_DEF_TYPE_VLAN,
+        NetdefType::Tunnel => NetplanDefType_NETPLAN_DEF_TYPE_TUNNEL,
+        NetdefType::Port => NetplanDefType_NETPLAN_DEF_TYPE_PORT,
+        NetdefType::Vrf => NetplanDefType_NETPLAN_DEF_TYPE_VRF,
+        NetdefType::Nm => NetplanDefType_NETPLAN_DEF_TYPE_NM,
+        NetdefType::Dummy => NetplanDefType_NETPLAN_DEF_TYPE_DUMMY,
+        NetdefType::Veth => NetplanDefType_NETPLAN_DEF_TYPE_VETH,
+    }
+}
+
+/* The returned string is static, it's the one libnetplan uses for the
+   top-level YAML keys, e.g. "ethernets". It's NULL for NetdefType::None.
+*/
+pub(crate) fn netdef_type_raw_name(netdef_type: NetdefType) -> *const i8 {
+    unsafe { netplan_def_type_name(netdef_type_to_raw(netdef_type)) }
+}
+
+pub(crate) fn backend_from_raw(backend: NetplanBackend) -> Backend {
+    match backend {
+        NetplanBackend_NETPLAN_BACKEND_NETWORKD => Backend::Networkd,
+        NetplanBackend_NETPLAN_BACKEND_NM => Backend::NetworkManager,
+        NetplanBackend_NETPLAN_BACKEND_OVS => Backend::Ovs,
+        _ => Backend::None,
+    }
+}
+
+pub(crate) fn backend_to_raw(backend: Backend) -> NetplanBackend {
+    match backend {
+        Backend::None => NetplanBackend_NETPLAN_BACKEND_NONE,
+        Backend::Networkd => NetplanBackend_NETPLAN_BACKEND_NETWORKD,
+        Backend::NetworkManager => NetplanBackend_NETPLAN_BACKEND_NM,
+        Backend::Ovs => NetplanBackend_NETPLAN_BACKEND_OVS,
+    }
+}
+
+pub(crate) fn backend_name(backend: Backend) -> String {
+    let name = unsafe { netplan_backend_name(backend_to_raw(backend)) };
+    copy_raw_string(name).unwrap_or_default()
+}
+
+pub(crate) fn netdef_get_backend(netdef: *const NetplanNetDefinition) -> Backend {
+    backend_from_raw(unsafe { netplan_netdef_get_backend(netdef) })
+}
+
+pub(crate) fn state_get_backend(state: *const NetplanState) -> Backend {
+    backend_from_raw(unsafe { netplan_state_get_backend(state) })
+}
+
+pub(crate) fn netdef_get_link(
+    netdef: *const NetplanNetDefinition,
+    link_type: LinkType,
+) -> Option<*const NetplanNetDefinition> {
+    let link = unsafe {
+        match link_type {
+            LinkType::Bridge => netplan_netdef_get_bridge_link(netdef),
+            LinkType::Bond => netplan_netdef_get_bond_link(netdef),
+            LinkType::Peer => netplan_netdef_get_peer_link(netdef),
+            LinkType::Vlan => netplan_netdef_get_vlan_link(netdef),
+            LinkType::Sriov => netplan_netdef_get_sriov_link(netdef),
+            LinkType::Vrf => netplan_netdef_get_vrf_link(netdef),
+        }
+    };
+
+    if link.is_null() {
+        None
+    } else {
+        Some(link as *const NetplanNetDefinition)
+    }
+}
+
 /* Simple wrapper around libc's memfd_create to avoid importing other crates
    memfd_create() is defined in netplan.h
 */
-pub(crate) fn netplan_memfd_create() -> Result<OwnedFd, String> {
-    unsafe {
-        let name_cstr = CString::new("netplan_memfd").unwrap();
-        let ret = memfd_create(name_cstr.as_ptr(), 0);
+pub(crate) fn netplan_memfd_create() -> NetplanResult<OwnedFd> {
+    let name = b"netplan_memfd\0";
+    let ret = unsafe { memfd_create(name.as_ptr() as *const c_char, 0) };
 
-        if ret >= 0 {
-            return Ok(OwnedFd::from_raw_fd(ret));
-        } else {
-            return Err("memfd_create failed".to_string());
-        }
+    if ret < 0 {
+        return Err(crate::error::NetplanError::io(
+            "memfd_create failed",
+            &io::Error::last_os_error(),
+        ));
     }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(ret) })
+}
+
+/* A memory file holding `contents`, rewound so libnetplan reads it all */
+pub(crate) fn netplan_memfd_from_bytes(contents: &[u8]) -> NetplanResult<File> {
+    let mut file = File::from(netplan_memfd_create()?);
+
+    file.write_all(contents)
+        .and_then(|_| file.rewind())
+        .map_err(|e| crate::error::NetplanError::io("cannot write to memory file", &e))?;
+
+    Ok(file)
+}
+
+pub(crate) fn netplan_memfd_read(file: &mut File) -> NetplanResult<String> {
+    let mut contents = String::new();
+
+    file.rewind()
+        .and_then(|_| file.read_to_string(&mut contents))
+        .map_err(|e| crate::error::NetplanError::io("cannot read from memory file", &e))?;
+
+    Ok(contents)
+}
+
+/* Paths don't have to be UTF-8, so their raw bytes are passed on */
+pub(crate) fn path_to_cstring(path: &Path) -> NetplanResult<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| {
+        crate::error::NetplanError::generic(&format!("invalid path {}: {e}", path.display()))
+    })
+}
+
+pub(crate) fn to_cstring(value: &str) -> NetplanResult<CString> {
+    CString::new(value)
+        .map_err(|e| crate::error::NetplanError::generic(&format!("invalid string {value:?}: {e}")))
 }
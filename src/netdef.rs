@@ -1,7 +1,42 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::net::IpAddr;
+
+use crate::address::Address;
+use crate::address::AddressIter;
+use crate::dns::NameserverIter;
+use crate::dns::SearchDomainIter;
+use crate::error::NetplanError;
+use crate::error::NetplanResult;
+use crate::interface::InterfaceInfo;
+use crate::libnetplan::backend_name;
+use crate::libnetplan::copy_raw_string;
+use crate::libnetplan::netdef_get_backend;
+use crate::libnetplan::netdef_get_critical;
+use crate::libnetplan::netdef_get_delay_virtual_functions_rebind;
+use crate::libnetplan::netdef_get_dhcp4;
+use crate::libnetplan::netdef_get_dhcp6;
+use crate::libnetplan::netdef_get_embedded_switch_mode;
+use crate::libnetplan::netdef_get_filepath;
 use crate::libnetplan::netdef_get_id;
+use crate::libnetplan::netdef_get_link;
+use crate::libnetplan::netdef_get_link_local_ipv4;
+use crate::libnetplan::netdef_get_link_local_ipv6;
+use crate::libnetplan::netdef_get_macaddress;
+use crate::libnetplan::netdef_get_set_name;
+use crate::libnetplan::netdef_get_sriov_vlan_filter;
 use crate::libnetplan::netdef_get_type;
+use crate::libnetplan::netdef_get_vlan_id;
+use crate::libnetplan::netdef_has_match;
+use crate::libnetplan::netdef_match_interface;
+use crate::libnetplan::netdef_type_raw_name;
 use crate::libnetplan::NetplanNetDefinition;
+use crate::route::Route;
+use crate::route::RouteIter;
+use crate::state::State;
+use crate::topology::LinkType;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NetdefType {
     None,
     Ethernet,
@@ -18,19 +53,292 @@ pub enum NetdefType {
     Veth,
 }
 
+impl NetdefType {
+    /// The name libnetplan uses for the type, which is also its top-level
+    /// YAML key, e.g. "ethernets".
+    pub fn name(&self) -> Option<String> {
+        copy_raw_string(netdef_type_raw_name(*self))
+    }
+
+    /// Ethernets, wifis and modems are backed by existing hardware, which
+    /// they select through their `match` stanza or their ID.
+    pub fn is_physical(&self) -> bool {
+        matches!(
+            self,
+            NetdefType::Ethernet | NetdefType::Wifi | NetdefType::Modem
+        )
+    }
+}
+
+/// The renderer in charge of a netdef, or of a whole State.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    None,
+    Networkd,
+    NetworkManager,
+    Ovs,
+}
+
+impl Backend {
+    /// The name libnetplan uses for the backend, e.g. "NetworkManager".
+    pub fn name(&self) -> String {
+        backend_name(*self)
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A copy of a netdef's settings, independent from its State.
+///
+/// It has no matches(): libnetplan doesn't expose the fields of a `match`
+/// stanza, only netplan_netdef_match_interface() on the netdef itself, so
+/// use NetdefRef::matches() while the State is around.
+#[derive(Debug, Clone)]
 pub struct Netdef {
     pub id: String,
     pub r#type: NetdefType,
+    pub backend: Backend,
+    pub filepath: Option<String>,
+    pub set_name: Option<String>,
+    pub macaddress: Option<String>,
+    pub dhcp4: bool,
+    pub dhcp6: bool,
+    pub link_local_ipv4: bool,
+    pub link_local_ipv6: bool,
+    pub has_match: bool,
+    pub critical: bool,
+    pub vlan_id: Option<u32>,
+    addresses: Vec<Address>,
+    routes: Vec<Route>,
+    nameservers: Vec<IpAddr>,
+    search_domains: Vec<String>,
 }
 
 impl Netdef {
-    pub(crate) fn from_raw_netdef(raw_netdef: *const NetplanNetDefinition) -> Self {
-        let id = netdef_get_id(raw_netdef).expect("Failed to get netdef ID.");
-        let netdef_type = netdef_get_type(raw_netdef);
+    pub(crate) fn from_raw_netdef(raw_netdef: *const NetplanNetDefinition) -> NetplanResult<Self> {
+        Netdef::try_from(NetdefRef::from_raw_netdef(raw_netdef))
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.addresses.iter()
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter()
+    }
+
+    pub fn nameservers(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.nameservers.iter().copied()
+    }
+
+    pub fn search_domains(&self) -> impl Iterator<Item = &str> {
+        self.search_domains.iter().map(|domain| domain.as_str())
+    }
+}
 
-        Netdef {
-            id,
-            r#type: netdef_type,
+impl TryFrom<NetdefRef<'_>> for Netdef {
+    type Error = NetplanError;
+
+    fn try_from(netdef: NetdefRef<'_>) -> NetplanResult<Self> {
+        Ok(Netdef {
+            id: netdef.id()?,
+            r#type: netdef.r#type(),
+            backend: netdef.backend(),
+            filepath: netdef.filepath()?,
+            set_name: netdef.set_name()?,
+            macaddress: netdef.macaddress()?,
+            dhcp4: netdef.dhcp4(),
+            dhcp6: netdef.dhcp6(),
+            link_local_ipv4: netdef.link_local_ipv4(),
+            link_local_ipv6: netdef.link_local_ipv6(),
+            has_match: netdef.has_match(),
+            critical: netdef.critical(),
+            vlan_id: netdef.vlan_id(),
+            addresses: netdef.addresses().collect::<NetplanResult<_>>()?,
+            routes: netdef.routes().collect::<NetplanResult<_>>()?,
+            nameservers: netdef.nameservers().collect::<NetplanResult<_>>()?,
+            search_domains: netdef.search_domains().collect(),
+        })
+    }
+}
+
+/// A handle to a netdef owned by a State. Nothing is copied out of
+/// libnetplan until one of the getters is called, and the handle can't
+/// outlive the State it was obtained from.
+#[derive(Clone, Copy)]
+pub struct NetdefRef<'state> {
+    netdef: *const NetplanNetDefinition,
+    _state: PhantomData<&'state State>,
+}
+
+impl<'state> NetdefRef<'state> {
+    /* The caller must make sure the pointer belongs to a State that lives
+       for 'state.
+    */
+    pub(crate) fn from_raw_netdef(netdef: *const NetplanNetDefinition) -> Self {
+        NetdefRef {
+            netdef,
+            _state: PhantomData,
         }
     }
+
+    pub(crate) fn as_ptr(&self) -> *const NetplanNetDefinition {
+        self.netdef
+    }
+
+    pub fn id(&self) -> NetplanResult<String> {
+        netdef_get_id(self.netdef).map_err(|e| getter_error("ID", e))
+    }
+
+    pub fn r#type(&self) -> NetdefType {
+        netdef_get_type(self.netdef)
+    }
+
+    pub fn backend(&self) -> Backend {
+        netdef_get_backend(self.netdef)
+    }
+
+    pub fn filepath(&self) -> NetplanResult<Option<String>> {
+        netdef_get_filepath(self.netdef).map_err(|e| getter_error("filepath", e))
+    }
+
+    pub fn set_name(&self) -> NetplanResult<Option<String>> {
+        netdef_get_set_name(self.netdef).map_err(|e| getter_error("set-name", e))
+    }
+
+    pub fn macaddress(&self) -> NetplanResult<Option<String>> {
+        netdef_get_macaddress(self.netdef).map_err(|e| getter_error("macaddress", e))
+    }
+
+    pub fn dhcp4(&self) -> bool {
+        netdef_get_dhcp4(self.netdef)
+    }
+
+    pub fn dhcp6(&self) -> bool {
+        netdef_get_dhcp6(self.netdef)
+    }
+
+    pub fn link_local_ipv4(&self) -> bool {
+        netdef_get_link_local_ipv4(self.netdef)
+    }
+
+    pub fn link_local_ipv6(&self) -> bool {
+        netdef_get_link_local_ipv6(self.netdef)
+    }
+
+    pub fn has_match(&self) -> bool {
+        netdef_has_match(self.netdef)
+    }
+
+    pub fn critical(&self) -> bool {
+        netdef_get_critical(self.netdef)
+    }
+
+    pub fn vlan_id(&self) -> Option<u32> {
+        netdef_get_vlan_id(self.netdef)
+    }
+
+    /// The SR-IOV embedded-switch-mode of a PF, "switchdev" or "legacy".
+    pub fn embedded_switch_mode(&self) -> NetplanResult<Option<String>> {
+        netdef_get_embedded_switch_mode(self.netdef)
+            .map_err(|e| getter_error("embedded-switch-mode", e))
+    }
+
+    /// Whether the PF's VFs should stay unbound after changing the
+    /// embedded switch mode.
+    pub fn delay_virtual_functions_rebind(&self) -> bool {
+        netdef_get_delay_virtual_functions_rebind(self.netdef)
+    }
+
+    /// Whether the VLAN is filtered in hardware by the VF it links to,
+    /// i.e. it uses the `sriov` renderer.
+    pub fn sriov_vlan_filter(&self) -> bool {
+        netdef_get_sriov_vlan_filter(self.netdef)
+    }
+
+    /// The static addresses of the netdef. Entries libnetplan returns that
+    /// can't be parsed are yielded as errors.
+    pub fn addresses(&self) -> impl Iterator<Item = NetplanResult<Address>> + 'state {
+        AddressIter::new(self.netdef).map(|address| address.map_err(|e| NetplanError::generic(&e)))
+    }
+
+    /// The static routes of the netdef. Entries libnetplan returns that
+    /// can't be parsed are yielded as errors.
+    pub fn routes(&self) -> impl Iterator<Item = NetplanResult<Route>> + 'state {
+        RouteIter::new(self.netdef).map(|route| route.map_err(|e| NetplanError::generic(&e)))
+    }
+
+    pub fn nameservers(&self) -> impl Iterator<Item = NetplanResult<IpAddr>> + 'state {
+        NameserverIter::new(self.netdef)
+            .map(|nameserver| nameserver.map_err(|e| NetplanError::generic(&e)))
+    }
+
+    pub fn search_domains(&self) -> impl Iterator<Item = String> + 'state {
+        SearchDomainIter::new(self.netdef)
+    }
+
+    /// Whether the netdef applies to `interface`, according to its `match`
+    /// stanza or, if it has none, its ID.
+    pub fn matches(&self, interface: &InterfaceInfo) -> bool {
+        netdef_match_interface(
+            self.netdef,
+            &interface.name,
+            interface.mac.as_deref(),
+            interface.driver.as_deref(),
+        )
+    }
+
+    /// The netdef this one links to through `link_type`, if any.
+    pub fn link(&self, link_type: LinkType) -> Option<NetdefRef<'state>> {
+        netdef_get_link(self.netdef, link_type).map(NetdefRef::from_raw_netdef)
+    }
+
+    pub fn bridge_link(&self) -> Option<NetdefRef<'state>> {
+        self.link(LinkType::Bridge)
+    }
+
+    pub fn bond_link(&self) -> Option<NetdefRef<'state>> {
+        self.link(LinkType::Bond)
+    }
+
+    pub fn peer_link(&self) -> Option<NetdefRef<'state>> {
+        self.link(LinkType::Peer)
+    }
+
+    pub fn vlan_link(&self) -> Option<NetdefRef<'state>> {
+        self.link(LinkType::Vlan)
+    }
+
+    pub fn sriov_link(&self) -> Option<NetdefRef<'state>> {
+        self.link(LinkType::Sriov)
+    }
+
+    pub fn vrf_link(&self) -> Option<NetdefRef<'state>> {
+        self.link(LinkType::Vrf)
+    }
+}
+
+fn getter_error(field: &str, error: String) -> NetplanError {
+    NetplanError::generic(&format!("failed to get netdef {field}: {error}"))
+}
+
+impl fmt::Debug for NetdefRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetdefRef")
+            .field("id", &self.id().ok())
+            .field("type", &self.r#type())
+            .finish()
+    }
+}
+
+impl PartialEq for NetdefRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.netdef == other.netdef
+    }
 }
+
+impl Eq for NetdefRef<'_> {}
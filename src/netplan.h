@@ -8,7 +8,13 @@ typedef struct GError NetplanError;
 typedef struct netplan_parser NetplanParser;
 typedef struct netplan_state NetplanState;
 typedef struct netplan_net_definition NetplanNetDefinition;
-typedef enum { a, } NetplanBackend;
+typedef enum {
+    NETPLAN_BACKEND_NONE,
+    NETPLAN_BACKEND_NETWORKD,
+    NETPLAN_BACKEND_NM,
+    NETPLAN_BACKEND_OVS,
+    NETPLAN_BACKEND_MAX_,
+} NetplanBackend;
 typedef enum {
     NETPLAN_DEF_TYPE_NONE,
     /* physical devices */
@@ -65,6 +71,7 @@ typedef struct {
 // Error handling
 uint64_t netplan_error_code(NetplanError* error);
 ssize_t netplan_error_message(NetplanError* error, char* buf, size_t buf_size);
+void netplan_error_clear(NetplanError** error);
 
 // Parser
 NetplanParser* netplan_parser_new();
@@ -1,10 +1,13 @@
-use std::ffi::CString;
-use std::fs::File;
-use std::io::Seek;
-use std::io::Write;
-use std::os::fd::AsRawFd;
-
-use crate::libnetplan::netplan_memfd_create;
+use std::fs;
+use std::io::{self, Read};
+use std::os::fd::{AsFd, AsRawFd};
+use std::path::{Path, PathBuf};
+
+use crate::error::NetplanError;
+use crate::error::NetplanResult;
+use crate::hierarchy::HierarchyLayer;
+use crate::hierarchy::VirtualHierarchy;
+use crate::libnetplan::netplan_memfd_from_bytes;
 use crate::libnetplan::netplan_parser_clear;
 use crate::libnetplan::netplan_parser_load_keyfile;
 use crate::libnetplan::netplan_parser_load_nullable_fields;
@@ -13,11 +16,10 @@ use crate::libnetplan::netplan_parser_load_yaml;
 use crate::libnetplan::netplan_parser_load_yaml_from_fd;
 use crate::libnetplan::netplan_parser_load_yaml_hierarchy;
 use crate::libnetplan::netplan_parser_new;
-use crate::libnetplan::LibNetplanError;
-use crate::libnetplan::NetplanError;
-use crate::libnetplan::NetplanErrorDomains;
+use crate::libnetplan::path_to_cstring;
+use crate::libnetplan::to_cstring;
+use crate::libnetplan::GError;
 use crate::libnetplan::NetplanParser;
-use crate::libnetplan::NetplanResult;
 
 pub struct Parser {
     parser: *mut NetplanParser,
@@ -34,131 +36,265 @@ impl Parser {
         self.parser
     }
 
-    pub fn load_yaml_hierarchy(&mut self, root_dir: &str) -> NetplanResult<()> {
-        let path = CString::new(root_dir).unwrap();
-        let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+    pub fn load_yaml_hierarchy(&mut self, root_dir: impl AsRef<Path>) -> NetplanResult<()> {
+        let path = path_to_cstring(root_dir.as_ref())?;
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
         let ret = unsafe {
             netplan_parser_load_yaml_hierarchy(self.as_mut_ptr(), path.as_ptr(), &mut netplan_error)
         };
 
-        if ret == 0 && !netplan_error.is_null() {
-            if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
-                return Err(NetplanErrorDomains::from_libnetplan_error(&error));
-            }
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "load_yaml_hierarchy",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Same as load_yaml_hierarchy(), for a hierarchy held in memory. Errors
+    /// point to the files by their path relative to the root directory,
+    /// e.g. "etc/netplan/10-config.yaml".
+    pub fn load_virtual_hierarchy(&mut self, hierarchy: &VirtualHierarchy) -> NetplanResult<()> {
+        for file in hierarchy.files() {
+            self.load_virtual_yaml(&file.path(), file.yaml)?;
         }
 
         Ok(())
     }
 
-    pub fn load_yaml(&mut self, filename: &str) -> NetplanResult<()> {
-        let path = CString::new(filename).unwrap();
-        let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+    pub(crate) fn load_virtual_yaml(&mut self, path: &Path, yaml: &[u8]) -> NetplanResult<()> {
+        /* libnetplan doesn't know the name of a file loaded from a fd */
+        self.load_yaml_from_bytes(yaml).map_err(|mut error| {
+            if error.line.is_some() {
+                error.file = Some(path.to_path_buf());
+            }
+            error
+        })
+    }
+
+    pub fn load_yaml(&mut self, filename: impl AsRef<Path>) -> NetplanResult<()> {
+        let path = path_to_cstring(filename.as_ref())?;
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
         let ret = unsafe {
             netplan_parser_load_yaml(self.as_mut_ptr(), path.as_ptr(), &mut netplan_error)
         };
 
-        if ret == 0 && !netplan_error.is_null() {
-            if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
-                return Err(NetplanErrorDomains::from_libnetplan_error(&error));
-            }
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(netplan_error, "load_yaml"));
         }
 
         Ok(())
     }
 
     pub fn load_yaml_from_string(&mut self, yaml: &str) -> NetplanResult<()> {
-        let memfd = netplan_memfd_create().unwrap();
+        self.load_yaml_from_bytes(yaml.as_bytes())
+    }
 
-        let mut file = File::from(memfd);
-        let _ = file.write(yaml.as_bytes());
-        let _ = file.flush();
-        let _ = file.rewind();
+    /// Loads a YAML document that isn't necessarily valid UTF-8.
+    pub fn load_yaml_from_bytes(&mut self, yaml: &[u8]) -> NetplanResult<()> {
+        self.load_yaml_from_fd(netplan_memfd_from_bytes(yaml)?)
+    }
+
+    /// Loads a YAML document read until the end of `reader`, e.g. an entry
+    /// of an archive or a socket.
+    pub fn load_yaml_from_reader(&mut self, mut reader: impl Read) -> NetplanResult<()> {
+        let mut yaml = Vec::new();
 
-        let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+        reader
+            .read_to_end(&mut yaml)
+            .map_err(|e| NetplanError::io("cannot read YAML", &e))?;
+
+        self.load_yaml_from_bytes(&yaml)
+    }
+
+    /// Loads a YAML document read from the current offset of `fd` until its
+    /// end. The fd stays open and owned by the caller.
+    pub fn load_yaml_from_fd(&mut self, fd: impl AsFd) -> NetplanResult<()> {
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
         let ret = unsafe {
-            netplan_parser_load_yaml_from_fd(self.parser, file.as_raw_fd(), &mut netplan_error)
+            netplan_parser_load_yaml_from_fd(
+                self.parser,
+                fd.as_fd().as_raw_fd(),
+                &mut netplan_error,
+            )
         };
 
-        if ret == 0 && !netplan_error.is_null() {
-            if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
-                return Err(NetplanErrorDomains::from_libnetplan_error(&error));
-            }
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "load_yaml_from_fd",
+            ));
         }
 
         Ok(())
     }
 
-    pub fn load_keyfile(&mut self, filename: &str) -> NetplanResult<()> {
-        let path = CString::new(filename).unwrap();
-        let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+    pub fn load_keyfile(&mut self, filename: impl AsRef<Path>) -> NetplanResult<()> {
+        let path = path_to_cstring(filename.as_ref())?;
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
         let ret =
             unsafe { netplan_parser_load_keyfile(self.parser, path.as_ptr(), &mut netplan_error) };
 
-        if ret == 0 && !netplan_error.is_null() {
-            if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
-                return Err(NetplanErrorDomains::from_libnetplan_error(&error));
-            }
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(netplan_error, "load_keyfile"));
         }
 
         Ok(())
     }
 
     pub fn load_nullable_fields(&mut self, yaml: &str) -> NetplanResult<()> {
-        let memfd = netplan_memfd_create().unwrap();
+        self.load_nullable_fields_from_bytes(yaml.as_bytes())
+    }
 
-        let mut file = File::from(memfd);
-        let _ = file.write(yaml.as_bytes());
-        let _ = file.flush();
-        let _ = file.rewind();
+    pub fn load_nullable_fields_from_bytes(&mut self, yaml: &[u8]) -> NetplanResult<()> {
+        self.load_nullable_fields_from_fd(netplan_memfd_from_bytes(yaml)?)
+    }
 
-        let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+    pub fn load_nullable_fields_from_reader(&mut self, mut reader: impl Read) -> NetplanResult<()> {
+        let mut yaml = Vec::new();
+
+        reader
+            .read_to_end(&mut yaml)
+            .map_err(|e| NetplanError::io("cannot read YAML", &e))?;
+
+        self.load_nullable_fields_from_bytes(&yaml)
+    }
+
+    pub fn load_nullable_fields_from_fd(&mut self, fd: impl AsFd) -> NetplanResult<()> {
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
         let ret = unsafe {
-            netplan_parser_load_nullable_fields(self.parser, file.as_raw_fd(), &mut netplan_error)
+            netplan_parser_load_nullable_fields(
+                self.parser,
+                fd.as_fd().as_raw_fd(),
+                &mut netplan_error,
+            )
         };
 
-        if ret == 0 && !netplan_error.is_null() {
-            if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
-                return Err(NetplanErrorDomains::from_libnetplan_error(&error));
-            }
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "load_nullable_fields",
+            ));
         }
 
         Ok(())
     }
 
     pub fn load_nullable_overrides(&mut self, yaml: &str, constraints: &str) -> NetplanResult<()> {
-        let memfd = netplan_memfd_create().unwrap();
-        let constraints_cstr = CString::new(constraints).unwrap();
-        let mut netplan_error = ::std::ptr::null_mut::<NetplanError>();
+        self.load_nullable_overrides_from_bytes(yaml.as_bytes(), constraints)
+    }
+
+    pub fn load_nullable_overrides_from_bytes(
+        &mut self,
+        yaml: &[u8],
+        constraints: &str,
+    ) -> NetplanResult<()> {
+        self.load_nullable_overrides_from_fd(netplan_memfd_from_bytes(yaml)?, constraints)
+    }
+
+    pub fn load_nullable_overrides_from_reader(
+        &mut self,
+        mut reader: impl Read,
+        constraints: &str,
+    ) -> NetplanResult<()> {
+        let mut yaml = Vec::new();
+
+        reader
+            .read_to_end(&mut yaml)
+            .map_err(|e| NetplanError::io("cannot read YAML", &e))?;
+
+        self.load_nullable_overrides_from_bytes(&yaml, constraints)
+    }
 
-        let mut file = File::from(memfd);
-        let _ = file.write(yaml.as_bytes());
-        let _ = file.flush();
-        let _ = file.rewind();
+    pub fn load_nullable_overrides_from_fd(
+        &mut self,
+        fd: impl AsFd,
+        constraints: &str,
+    ) -> NetplanResult<()> {
+        let constraints_cstr = to_cstring(constraints)?;
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
         let ret = unsafe {
             netplan_parser_load_nullable_overrides(
                 self.parser,
-                file.as_raw_fd(),
+                fd.as_fd().as_raw_fd(),
                 constraints_cstr.as_ptr(),
                 &mut netplan_error,
             )
         };
 
-        if ret == 0 && !netplan_error.is_null() {
-            if let Some(error) = LibNetplanError::try_from_raw_error(netplan_error) {
-                return Err(NetplanErrorDomains::from_libnetplan_error(&error));
-            }
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "load_nullable_overrides",
+            ));
         }
 
         Ok(())
     }
 }
 
+/* Every YAML file of the hierarchy at `root_dir`, shadowed or not, as
+   (layer, name, path). They are sorted by name, then by layer, so a file
+   comes right before the one shadowing it. Like libnetplan's glob, names
+   starting with a dot are skipped.
+*/
+pub(crate) fn hierarchy_layer_files(
+    root_dir: &Path,
+) -> io::Result<Vec<(HierarchyLayer, String, PathBuf)>> {
+    let mut files: Vec<(HierarchyLayer, String, PathBuf)> = Vec::new();
+
+    for layer in HierarchyLayer::ALL {
+        let entries = match fs::read_dir(root_dir.join(layer.dir())) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if path.extension().and_then(|e| e.to_str()) != Some("yaml") || name.starts_with('.') {
+                continue;
+            }
+
+            files.push((layer, name, path));
+        }
+    }
+
+    files.sort_by(|a, b| (&a.1, a.0).cmp(&(&b.1, b.0)));
+
+    Ok(files)
+}
+
+/* The YAML files libnetplan loads from a hierarchy, in loading order: a file
+   shadows the files with the same name in lower precedence directories, and
+   the remaining files are sorted by name.
+*/
+pub(crate) fn hierarchy_files(root_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let files = hierarchy_layer_files(root_dir)?;
+
+    Ok(files
+        .iter()
+        .enumerate()
+        .filter(|(index, (_, name, _))| {
+            files.get(index + 1).is_none_or(|(_, next, _)| next != name)
+        })
+        .map(|(_, (_, _, path))| path.clone())
+        .collect())
+}
+
 impl Drop for Parser {
     fn drop(&mut self) {
         unsafe { netplan_parser_clear(&mut self.parser) };
@@ -167,13 +303,16 @@ impl Drop for Parser {
 
 #[cfg(test)]
 mod tests {
+    use crate::error::ErrorDomain;
     use crate::state::State;
     use crate::utils::netplan_create_yaml_patch;
 
     use super::*;
+    use std::ffi::OsStr;
     use std::fs::DirBuilder;
     use std::fs::{self, File};
-    use std::io::Write;
+    use std::io::{Seek, Write};
+    use std::os::unix::ffi::OsStrExt;
     use std::os::unix::fs::PermissionsExt;
     use tempfile::tempdir;
 
@@ -264,10 +403,11 @@ network:
         assert!(parser_result.is_err());
 
         if let Err(error) = parser_result {
-            if let NetplanErrorDomains::NetplanFileError(error_message) = error {
-                assert!(error_message
-                    .contains("Error in network definition: invalid boolean value 'badvalue'"));
-            }
+            assert!(error
+                .message
+                .contains("Error in network definition: invalid boolean value 'badvalue'"));
+            assert_eq!(error.domain, ErrorDomain::Parser);
+            assert!(error.line.is_some());
         }
 
         fs::remove_file(root_dir.path().join("etc/netplan/10-config.yaml"))
@@ -278,6 +418,87 @@ network:
         root_dir.close().expect("Cannot close directory");
     }
 
+    #[test]
+    fn test_load_virtual_hierarchy() {
+        let mut hierarchy = VirtualHierarchy::new();
+        hierarchy
+            .insert(
+                HierarchyLayer::Lib,
+                "10-config.yaml",
+                "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n",
+            )
+            .unwrap();
+        hierarchy
+            .insert(
+                HierarchyLayer::Etc,
+                "10-config.yaml",
+                "network:\n  ethernets:\n    eth0:\n      dhcp6: true\n",
+            )
+            .unwrap();
+        hierarchy
+            .insert(
+                HierarchyLayer::Run,
+                "20-config.yaml",
+                "network:\n  ethernets:\n    eth0:\n      mtu: 9000\n",
+            )
+            .unwrap();
+
+        let mut parser = Parser::new();
+        parser.load_virtual_hierarchy(&hierarchy).unwrap();
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+        let dump = state.dump_yaml().unwrap();
+        assert_eq!(
+            dump,
+            "network:\n  version: 2\n  ethernets:\n    eth0:\n      dhcp6: true\n      mtu: 9000\n"
+        );
+
+        hierarchy
+            .insert(
+                HierarchyLayer::Etc,
+                "30-bad.yaml",
+                "network:\n  ethernets:\n    eth1:\n      dhcp4: badvalue\n",
+            )
+            .unwrap();
+        let error = Parser::new()
+            .load_virtual_hierarchy(&hierarchy)
+            .unwrap_err();
+        assert_eq!(error.file, Some(PathBuf::from("etc/netplan/30-bad.yaml")));
+        assert_eq!(error.line, Some(4));
+    }
+
+    #[test]
+    fn test_hierarchy_files() {
+        let root_dir = tempdir().unwrap();
+
+        for layer in HierarchyLayer::ALL {
+            DirBuilder::new()
+                .recursive(true)
+                .create(root_dir.path().join(layer.dir()))
+                .unwrap();
+        }
+
+        for file in [
+            "lib/netplan/10-config.yaml",
+            "etc/netplan/10-config.yaml",
+            "etc/netplan/.hidden.yaml",
+            "etc/netplan/.yaml",
+            "etc/netplan/20-config.yml",
+            "run/netplan/05-config.yaml",
+        ] {
+            File::create(root_dir.path().join(file)).unwrap();
+        }
+
+        assert_eq!(
+            hierarchy_files(root_dir.path()).unwrap(),
+            vec![
+                root_dir.path().join("run/netplan/05-config.yaml"),
+                root_dir.path().join("etc/netplan/10-config.yaml"),
+            ]
+        );
+    }
+
     #[test]
     fn test_load_yaml_ok() {
         let root_dir = tempdir().expect("Cannot create tempdir for test");
@@ -355,10 +576,11 @@ network:
         assert!(parser_result.is_err());
 
         if let Err(error) = parser_result {
-            if let NetplanErrorDomains::NetplanFileError(error_message) = error {
-                assert!(error_message
-                    .contains("Error in network definition: invalid boolean value 'badvalue'"));
-            }
+            assert!(error
+                .message
+                .contains("Error in network definition: invalid boolean value 'badvalue'"));
+            assert_eq!(error.domain, ErrorDomain::Parser);
+            assert!(error.line.is_some());
         }
 
         fs::remove_file(root_dir.path().join("10-config.yaml")).expect("Cannot remove file");
@@ -376,7 +598,7 @@ network:
         let mut parser = Parser::new();
         let _ = parser.load_yaml_from_string(&yaml);
 
-        let state = State::new();
+        let mut state = State::new();
         state.import_parser_state(parser).unwrap();
         let dump = state.dump_yaml().unwrap();
         assert_eq!(
@@ -385,6 +607,57 @@ network:
         );
     }
 
+    #[test]
+    fn test_load_yaml_from_reader_and_fd() {
+        let mut parser = Parser::new();
+        parser
+            .load_yaml_from_reader(&b"network:\n  ethernets:\n    eth0:\n      dhcp4: true\n"[..])
+            .unwrap();
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"network:\n  ethernets:\n    eth1:\n      dhcp6: true\n")
+            .unwrap();
+        file.rewind().unwrap();
+        parser.load_yaml_from_fd(&file).unwrap();
+
+        /* Not UTF-8, but libnetplan only cares about the YAML structure */
+        parser
+            .load_yaml_from_bytes(b"# \xff\nnetwork:\n  ethernets:\n    eth2: {}\n")
+            .unwrap();
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+        assert_eq!(state.ids().unwrap(), vec!["eth0", "eth1", "eth2"]);
+    }
+
+    #[test]
+    fn test_load_yaml_non_utf8_path() {
+        let root_dir = tempdir().unwrap();
+        let path = root_dir.path().join(OsStr::from_bytes(b"\xff-config.yaml"));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"network:\n  ethernets:\n    eth0: {}\n")
+            .unwrap();
+
+        let mut parser = Parser::new();
+        parser.load_yaml(&path).unwrap();
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+        assert!(state.get_ref("eth0").is_some());
+    }
+
+    #[test]
+    fn test_load_nul_byte() {
+        let mut parser = Parser::new();
+
+        let error = parser.load_yaml("/etc/netplan/\0.yaml").unwrap_err();
+        assert_eq!(error.domain, ErrorDomain::Generic);
+        assert!(error.message.contains("nul byte"));
+
+        assert!(parser.load_yaml_hierarchy("/\0").is_err());
+        assert!(parser.load_keyfile("\0").is_err());
+    }
+
     #[test]
     fn test_load_keyfile_ok() {
         let root_dir = tempdir().expect("Cannot create tempdir for test");
@@ -449,9 +722,9 @@ address1=10.100.1.39/24"
         assert!(parser_result.is_err());
 
         if let Err(error) = parser_result {
-            if let NetplanErrorDomains::NetplanFileError(error_message) = error {
-                assert!(error_message.contains("Keyfile: cannot find connection.uuid"));
-            }
+            assert!(error
+                .message
+                .contains("Keyfile: cannot find connection.uuid"));
         }
 
         fs::remove_file(filename).expect("Cannot remove file");
@@ -501,7 +774,7 @@ network:
 
         parser.load_yaml(&filename_str).unwrap();
 
-        let state = State::new();
+        let mut state = State::new();
         state.import_parser_state(parser).unwrap();
 
         let dump = state.dump_yaml().unwrap();
@@ -509,4 +782,31 @@ network:
         // eth0 is gone
         assert_eq!(dump, "network:\n  version: 2\n  ethernets:\n    eth1: {}\n");
     }
+
+    #[test]
+    fn test_load_nullable_fields_from_bytes_reader_and_fd() {
+        let mut parser = Parser::new();
+        parser
+            .load_nullable_fields_from_bytes(b"network:\n  ethernets:\n    eth0: null\n")
+            .unwrap();
+        parser
+            .load_nullable_fields_from_reader(&b"network:\n  ethernets:\n    eth1: null\n"[..])
+            .unwrap();
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"network:\n  ethernets:\n    eth2: null\n")
+            .unwrap();
+        file.rewind().unwrap();
+        parser.load_nullable_fields_from_fd(&file).unwrap();
+
+        parser
+            .load_yaml_from_string(
+                "network:\n  ethernets:\n    eth0: {}\n    eth1: {}\n    eth2: {}\n    eth3: {}\n",
+            )
+            .unwrap();
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+        assert_eq!(state.ids().unwrap(), vec!["eth3"]);
+    }
 }
@@ -0,0 +1,740 @@
+use serde_json::Value as JsonValue;
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{NetplanError, NetplanResult};
+use crate::keypath::{KeyPath, ToKeyPath};
+use crate::state::State;
+
+/// A change to a single key: a new YAML value, or null to delete the key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchOperation {
+    key_path: KeyPath,
+    value: Value,
+}
+
+impl PatchOperation {
+    pub fn key_path(&self) -> &KeyPath {
+        &self.key_path
+    }
+
+    pub fn is_unset(&self) -> bool {
+        self.value.is_null()
+    }
+
+    /* Whether the operation gives `key_path` or one of its children a value */
+    fn sets(&self, key_path: &KeyPath) -> bool {
+        let segments = self.key_path.segments();
+
+        if segments.starts_with(key_path.segments()) {
+            return !self.is_unset();
+        }
+
+        match key_path.segments().strip_prefix(segments) {
+            Some(rest) => rest
+                .iter()
+                .try_fold(&self.value, |value, segment| value.get(segment.as_str()))
+                .is_some_and(|value| !value.is_null()),
+            None => false,
+        }
+    }
+}
+
+/// Set and unset operations on any number of keys, applied together by
+/// Config::apply() as a single override document. The keys it unsets and
+/// the sequences it sets are nulled before the configuration is loaded, so
+/// a sequence replaces the one netplan has instead of being appended to
+/// it. Config::apply() only replaces it in the file it writes to: the same
+/// key in other files of the hierarchy still adds to it.
+///
+/// Operations are applied in order: a later operation on the same key, or
+/// on one of its parents, replaces the earlier one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchSet {
+    operations: Vec<PatchOperation>,
+}
+
+impl PatchSet {
+    pub fn new() -> Self {
+        PatchSet::default()
+    }
+
+    /// Sets `key_path` to the YAML `value`, e.g. "[10.0.0.1/24]".
+    pub fn set(&mut self, key_path: impl ToKeyPath, value: &str) -> NetplanResult<&mut Self> {
+        let value: Value = serde_yaml::from_str(value)
+            .map_err(|e| NetplanError::generic(&format!("invalid value '{value}': {e}")))?;
+
+        self.push(key_path.to_key_path()?, value)
+    }
+
+    /// Deletes `key_path`, as setting it to null does.
+    pub fn unset(&mut self, key_path: impl ToKeyPath) -> NetplanResult<&mut Self> {
+        self.push(key_path.to_key_path()?, Value::Null)
+    }
+
+    /// The operations of an RFC 7386 JSON Merge Patch document, e.g.
+    /// `{"network": {"ethernets": {"eth0": {"dhcp4": null, "mtu": 9000}}}}`.
+    /// Nulls delete keys, objects are merged and anything else replaces the
+    /// current value. The top-level "network" key may be omitted.
+    pub fn from_merge_patch(merge_patch: &str) -> NetplanResult<Self> {
+        let merge_patch: JsonValue = serde_json::from_str(merge_patch)
+            .map_err(|e| NetplanError::generic(&format!("invalid merge patch: {e}")))?;
+
+        if !merge_patch.is_object() {
+            return Err(NetplanError::generic(
+                "invalid merge patch: the document must be an object",
+            ));
+        }
+
+        let mut patch_set = PatchSet::new();
+        patch_set.push_merge_patch(KeyPath::new(), &merge_patch)?;
+
+        Ok(patch_set)
+    }
+
+    /// The operations equivalent to applying the RFC 6902 JSON Patch
+    /// document `json_patch` to the configuration of `state`. Pointers are
+    /// relative to the whole document, e.g. "/network/ethernets/eth0/mtu",
+    /// and every operation, including "test", is checked against it.
+    pub fn from_json_patch(json_patch: &str, state: &State) -> NetplanResult<Self> {
+        let yaml: Value = serde_yaml::from_str(&state.dump_yaml()?)
+            .map_err(|e| NetplanError::generic(&format!("cannot parse state: {e}")))?;
+        let document = serde_json::to_value(yaml)
+            .map_err(|e| NetplanError::generic(&format!("cannot convert state: {e}")))?;
+
+        PatchSet::from_json_patch_document(json_patch, document)
+    }
+
+    fn from_json_patch_document(json_patch: &str, document: JsonValue) -> NetplanResult<Self> {
+        let operations: Vec<JsonValue> = serde_json::from_str(json_patch)
+            .map_err(|e| NetplanError::generic(&format!("invalid JSON patch: {e}")))?;
+
+        let mut patched = document.clone();
+        for operation in &operations {
+            json_patch::apply(&mut patched, operation)?;
+        }
+
+        /* Only what's under "network" is netplan configuration */
+        let network = |document: &JsonValue| {
+            let mut document = document.clone();
+            let network = document
+                .as_object_mut()
+                .and_then(|object| object.remove("network"));
+            (network.unwrap_or(JsonValue::Null), document)
+        };
+        let (original_network, original_rest) = network(&document);
+        let (patched_network, patched_rest) = network(&patched);
+
+        if original_rest != patched_rest {
+            return Err(NetplanError::generic(
+                "invalid JSON patch: only /network can be modified",
+            ));
+        }
+
+        let mut patch_set = PatchSet::new();
+        patch_set.push_diff(
+            KeyPath::from_segments(["network"]),
+            &original_network,
+            &patched_network,
+        )?;
+
+        Ok(patch_set)
+    }
+
+    fn push_merge_patch(
+        &mut self,
+        key_path: KeyPath,
+        merge_patch: &JsonValue,
+    ) -> NetplanResult<()> {
+        let object = match merge_patch.as_object() {
+            /* An empty document, or "network", changes nothing */
+            Some(object) if object.is_empty() && key_path.rooted().len() < 2 => return Ok(()),
+            Some(object) if !object.is_empty() => object,
+            _ => return self.push_json(key_path, merge_patch).map(|_| ()),
+        };
+
+        for (key, value) in object {
+            self.push_merge_patch(key_path.join(key.as_str()), value)?;
+        }
+
+        Ok(())
+    }
+
+    /* The operations turning `original` into `patched`: removed keys are
+       unset, objects are compared key by key and anything else that changed
+       is set as a whole.
+    */
+    fn push_diff(
+        &mut self,
+        key_path: KeyPath,
+        original: &JsonValue,
+        patched: &JsonValue,
+    ) -> NetplanResult<()> {
+        if original == patched {
+            return Ok(());
+        }
+
+        let (original, patched) = match (original.as_object(), patched.as_object()) {
+            (Some(original), Some(patched)) => (original, patched),
+            _ => return self.push_json(key_path, patched).map(|_| ()),
+        };
+
+        for key in original.keys().filter(|key| !patched.contains_key(*key)) {
+            self.push(key_path.join(key.as_str()), Value::Null)?;
+        }
+
+        for (key, value) in patched {
+            let child = key_path.join(key.as_str());
+            match original.get(key) {
+                Some(original) => self.push_diff(child, original, value)?,
+                None => {
+                    self.push_json(child, value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_json(&mut self, key_path: KeyPath, value: &JsonValue) -> NetplanResult<&mut Self> {
+        let value = serde_yaml::to_value(value)
+            .map_err(|e| NetplanError::generic(&format!("invalid value at '{key_path}': {e}")))?;
+
+        self.push(key_path, value)
+    }
+
+    pub(crate) fn push(&mut self, key_path: KeyPath, value: Value) -> NetplanResult<&mut Self> {
+        let key_path = key_path.rooted();
+
+        /* "network" itself can't be patched, only what's under it */
+        if key_path.len() < 2 {
+            return Err(NetplanError::generic(&format!(
+                "invalid key path '{key_path}': nothing to patch"
+            )));
+        }
+
+        self.operations.push(PatchOperation { key_path, value });
+
+        Ok(self)
+    }
+
+    pub fn operations(&self) -> &[PatchOperation] {
+        &self.operations
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// The override document merging all the operations, in the format
+    /// netplan_create_yaml_patch() produces for a single key.
+    pub fn to_yaml(&self) -> NetplanResult<String> {
+        to_yaml(self.operations.iter())
+    }
+
+    /// The document to load with Parser::load_nullable_fields() before the
+    /// configuration and to_yaml(): every key unset by an operation, and
+    /// every sequence set by one, is null in it. An unset key stays null
+    /// even if a later operation sets one of its children again, so the
+    /// earlier deletion isn't lost.
+    pub fn to_nullable_yaml(&self) -> NetplanResult<String> {
+        let mut document = Value::Mapping(Mapping::new());
+
+        for operation in &self.operations {
+            insert_nulls(
+                &mut document,
+                &mut operation.key_path.segments().to_vec(),
+                &operation.value,
+            );
+        }
+
+        serde_yaml::to_string(&document)
+            .map_err(|e| NetplanError::generic(&format!("cannot serialize patch: {e}")))
+    }
+
+    /// The keys the operations leave deleted: unset, and not set again by
+    /// a later operation. Children of a deleted key aren't listed.
+    pub fn deleted_keys(&self) -> Vec<&KeyPath> {
+        let deleted: Vec<&KeyPath> = self
+            .operations
+            .iter()
+            .enumerate()
+            .filter(|(index, operation)| {
+                operation.is_unset()
+                    && !self.operations[index + 1..]
+                        .iter()
+                        .any(|later| later.sets(&operation.key_path))
+            })
+            .map(|(_, operation)| &operation.key_path)
+            .collect();
+
+        deleted
+            .iter()
+            .copied()
+            .filter(|key_path| {
+                !deleted.iter().any(|parent| {
+                    parent.len() < key_path.len()
+                        && key_path.segments().starts_with(parent.segments())
+                })
+            })
+            .collect()
+    }
+}
+
+/* RFC 6902 operations on a JSON document */
+mod json_patch {
+    use serde_json::Value as JsonValue;
+
+    use crate::error::{NetplanError, NetplanResult};
+
+    fn invalid(message: &str) -> NetplanError {
+        NetplanError::generic(&format!("invalid JSON patch: {message}"))
+    }
+
+    fn string_member<'a>(operation: &'a JsonValue, member: &str) -> NetplanResult<&'a str> {
+        operation
+            .get(member)
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| invalid(&format!("missing '{member}' in {operation}")))
+    }
+
+    fn value_member(operation: &JsonValue) -> NetplanResult<JsonValue> {
+        operation
+            .get("value")
+            .cloned()
+            .ok_or_else(|| invalid(&format!("missing 'value' in {operation}")))
+    }
+
+    fn index(token: &str, len: usize, pointer: &str) -> NetplanResult<usize> {
+        match token.parse::<usize>() {
+            Ok(index) if index < len && (token == "0" || !token.starts_with('0')) => Ok(index),
+            _ => Err(invalid(&format!("'{pointer}' doesn't exist"))),
+        }
+    }
+
+    fn get<'a>(document: &'a JsonValue, pointer: &str) -> NetplanResult<&'a JsonValue> {
+        document
+            .pointer(pointer)
+            .ok_or_else(|| invalid(&format!("'{pointer}' doesn't exist")))
+    }
+
+    /* The container holding what `pointer` points to, and its unescaped
+       reference token in the container.
+    */
+    fn parent<'a>(
+        document: &'a mut JsonValue,
+        pointer: &str,
+    ) -> NetplanResult<(&'a mut JsonValue, String)> {
+        let (parent_pointer, token) = match pointer.rsplit_once('/') {
+            Some(split) => split,
+            None if pointer.is_empty() => {
+                return Err(invalid("the whole document can't be modified"))
+            }
+            None => return Err(invalid(&format!("'{pointer}' doesn't start with '/'"))),
+        };
+
+        let parent = document
+            .pointer_mut(parent_pointer)
+            .ok_or_else(|| invalid(&format!("'{parent_pointer}' doesn't exist")))?;
+
+        Ok((parent, token.replace("~1", "/").replace("~0", "~")))
+    }
+
+    fn add(document: &mut JsonValue, pointer: &str, value: JsonValue) -> NetplanResult<()> {
+        let (parent, last) = parent(document, pointer)?;
+
+        match parent {
+            JsonValue::Object(object) => {
+                object.insert(last, value);
+            }
+            JsonValue::Array(array) if last == "-" => array.push(value),
+            JsonValue::Array(array) => {
+                let index = index(&last, array.len() + 1, pointer)?;
+                array.insert(index, value);
+            }
+            _ => return Err(invalid(&format!("'{pointer}' has no parent container"))),
+        }
+
+        Ok(())
+    }
+
+    fn remove(document: &mut JsonValue, pointer: &str) -> NetplanResult<JsonValue> {
+        let (parent, last) = parent(document, pointer)?;
+
+        let removed = match parent {
+            JsonValue::Object(object) => object.remove(&last),
+            JsonValue::Array(array) => {
+                let index = index(&last, array.len(), pointer)?;
+                Some(array.remove(index))
+            }
+            _ => None,
+        };
+
+        removed.ok_or_else(|| invalid(&format!("'{pointer}' doesn't exist")))
+    }
+
+    pub(super) fn apply(document: &mut JsonValue, operation: &JsonValue) -> NetplanResult<()> {
+        let path = string_member(operation, "path")?;
+
+        match string_member(operation, "op")? {
+            "add" => add(document, path, value_member(operation)?),
+            "remove" => remove(document, path).map(|_| ()),
+            "replace" => {
+                remove(document, path)?;
+                add(document, path, value_member(operation)?)
+            }
+            "move" => {
+                let from = string_member(operation, "from")?;
+                if path.starts_with(&format!("{from}/")) {
+                    return Err(invalid(&format!("can't move '{from}' into itself")));
+                }
+                let value = remove(document, from)?;
+                add(document, path, value)
+            }
+            "copy" => {
+                let value = get(document, string_member(operation, "from")?)?.clone();
+                add(document, path, value)
+            }
+            "test" => {
+                if *get(document, path)? != value_member(operation)? {
+                    return Err(NetplanError::generic(&format!(
+                        "JSON patch test failed: unexpected value at '{path}'"
+                    )));
+                }
+                Ok(())
+            }
+            op => Err(invalid(&format!("unknown operation '{op}'"))),
+        }
+    }
+}
+
+/* The node at `segments` under `document`, added as a null if missing.
+   Anything on the way that isn't a mapping is replaced by an empty one.
+*/
+pub(crate) fn entry_mut<'a>(document: &'a mut Value, segments: &[String]) -> &'a mut Value {
+    let mut node = document;
+
+    for segment in segments {
+        if !node.is_mapping() {
+            *node = Value::Mapping(Mapping::new());
+        }
+
+        if let Value::Mapping(mapping) = node {
+            node = mapping
+                .entry(Value::String(segment.clone()))
+                .or_insert(Value::Null);
+        }
+    }
+
+    node
+}
+
+/* Sets `segments` to null in `document`, unless one of its parents already
+   is: nulling a parent covers its children.
+*/
+fn insert_null(document: &mut Value, segments: &[String]) {
+    let covered = (1..segments.len()).any(|len| {
+        segments[..len]
+            .iter()
+            .try_fold(&*document, |node, segment| node.get(segment.as_str()))
+            .is_some_and(Value::is_null)
+    });
+
+    if !covered {
+        *entry_mut(document, segments) = Value::Null;
+    }
+}
+
+/* Nulls `segments` in `document` if `value` is a null or a sequence, and
+   does the same for what's under it if it is a mapping
+*/
+fn insert_nulls(document: &mut Value, segments: &mut Vec<String>, value: &Value) {
+    match value {
+        Value::Null | Value::Sequence(_) => insert_null(document, segments),
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                if let Some(key) = key.as_str() {
+                    segments.push(key.to_string());
+                    insert_nulls(document, segments, value);
+                    segments.pop();
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+pub(crate) fn to_yaml<'a>(
+    operations: impl Iterator<Item = &'a PatchOperation>,
+) -> NetplanResult<String> {
+    let mut document = Value::Mapping(Mapping::new());
+
+    for operation in operations {
+        *entry_mut(&mut document, operation.key_path.segments()) = operation.value.clone();
+    }
+
+    serde_yaml::to_string(&document)
+        .map_err(|e| NetplanError::generic(&format!("cannot serialize patch: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_set_to_yaml() {
+        let mut patch_set = PatchSet::new();
+        patch_set
+            .set("ethernets.eth0.addresses", "[10.0.0.1/24]")
+            .unwrap()
+            .set("ethernets.eth0.routes", "[{to: default, via: 10.0.0.254}]")
+            .unwrap()
+            .unset("ethernets.eth0.dhcp4")
+            .unwrap()
+            .set(r#"vlans."eth0.100".id"#, "100")
+            .unwrap()
+            .set("network.ethernets.eth1", "{dhcp4: true}")
+            .unwrap()
+            .set("ethernets.eth1.dhcp6", "true")
+            .unwrap();
+
+        assert_eq!(patch_set.len(), 6);
+        assert!(patch_set.operations()[2].is_unset());
+        assert_eq!(
+            patch_set.to_yaml().unwrap(),
+            "network:
+  ethernets:
+    eth0:
+      addresses:
+      - 10.0.0.1/24
+      routes:
+      - to: default
+        via: 10.0.0.254
+      dhcp4: null
+    eth1:
+      dhcp4: true
+      dhcp6: true
+  vlans:
+    eth0.100:
+      id: 100
+"
+        );
+
+        patch_set.set("ethernets", "null").unwrap();
+        assert_eq!(
+            patch_set.to_yaml().unwrap(),
+            "network:\n  ethernets: null\n  vlans:\n    eth0.100:\n      id: 100\n"
+        );
+    }
+
+    #[test]
+    fn test_patch_set_to_nullable_yaml() {
+        let mut patch_set = PatchSet::new();
+        patch_set
+            .unset("ethernets.eth0")
+            .unwrap()
+            .set("ethernets.eth0.mtu", "1400")
+            .unwrap()
+            .unset("ethernets.eth1.dhcp4")
+            .unwrap()
+            .unset("ethernets.eth1.dhcp6")
+            .unwrap()
+            .set("ethernets.eth1", "{dhcp6: true, mtu: 1400}")
+            .unwrap()
+            .unset("ethernets.eth2.dhcp4")
+            .unwrap()
+            .unset("ethernets.eth2")
+            .unwrap();
+
+        /* The unset of eth0 survives the set of its child */
+        assert_eq!(
+            patch_set.to_yaml().unwrap(),
+            "network:
+  ethernets:
+    eth0:
+      mtu: 1400
+    eth1:
+      dhcp6: true
+      mtu: 1400
+    eth2: null
+"
+        );
+        assert_eq!(
+            patch_set.to_nullable_yaml().unwrap(),
+            "network:
+  ethernets:
+    eth0: null
+    eth1:
+      dhcp4: null
+      dhcp6: null
+    eth2: null
+"
+        );
+
+        let deleted: Vec<String> = patch_set
+            .deleted_keys()
+            .iter()
+            .map(|key_path| key_path.to_string())
+            .collect();
+        assert_eq!(
+            deleted,
+            vec!["network.ethernets.eth1.dhcp4", "network.ethernets.eth2"]
+        );
+    }
+
+    #[test]
+    fn test_patch_set_from_merge_patch() {
+        let patch_set = PatchSet::from_merge_patch(
+            r#"{"network": {"ethernets": {"eth0": {"dhcp4": null, "mtu": 9000, "nameservers": {"addresses": ["10.0.0.254"]}}, "eth1": {}}, "vlans": {"eth0.100": null}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            patch_set.to_yaml().unwrap(),
+            "network:
+  ethernets:
+    eth0:
+      dhcp4: null
+      mtu: 9000
+      nameservers:
+        addresses:
+        - 10.0.0.254
+    eth1: {}
+  vlans:
+    eth0.100: null
+"
+        );
+        assert_eq!(
+            patch_set.operations()[3].key_path().to_string(),
+            "network.ethernets.eth1"
+        );
+
+        for empty in ["{}", r#"{"network": {}}"#] {
+            assert!(PatchSet::from_merge_patch(empty).unwrap().is_empty());
+        }
+
+        assert!(PatchSet::from_merge_patch("[]").is_err());
+        assert!(PatchSet::from_merge_patch(r#"{"network": null}"#).is_err());
+    }
+
+    #[test]
+    fn test_patch_set_from_json_patch() {
+        let document = serde_json::json!({
+            "network": {
+                "version": 2,
+                "ethernets": {
+                    "eth0": {"dhcp4": true, "addresses": ["10.0.0.1/24", "10.0.0.2/24"]},
+                    "eth1": {"mtu": 1500}
+                }
+            }
+        });
+
+        let patch_set = PatchSet::from_json_patch_document(
+            r#"[
+                {"op": "test", "path": "/network/ethernets/eth1/mtu", "value": 1500},
+                {"op": "remove", "path": "/network/ethernets/eth0/dhcp4"},
+                {"op": "replace", "path": "/network/ethernets/eth0/addresses/1", "value": "10.0.0.3/24"},
+                {"op": "add", "path": "/network/ethernets/eth0/addresses/-", "value": "10.0.0.4/24"},
+                {"op": "copy", "from": "/network/ethernets/eth1/mtu", "path": "/network/ethernets/eth0/mtu"},
+                {"op": "move", "from": "/network/ethernets/eth1", "path": "/network/ethernets/eth2"},
+                {"op": "add", "path": "/network/vlans", "value": {"eth0.100": {"id": 100, "link": "eth0"}}}
+            ]"#,
+            document.clone(),
+        )
+        .unwrap();
+
+        let operations: Vec<(String, bool)> = patch_set
+            .operations()
+            .iter()
+            .map(|operation| (operation.key_path().to_string(), operation.is_unset()))
+            .collect();
+        assert_eq!(
+            operations,
+            vec![
+                ("network.ethernets.eth1".to_string(), true),
+                ("network.ethernets.eth0.dhcp4".to_string(), true),
+                ("network.ethernets.eth0.addresses".to_string(), false),
+                ("network.ethernets.eth0.mtu".to_string(), false),
+                ("network.ethernets.eth2".to_string(), false),
+                ("network.vlans".to_string(), false),
+            ]
+        );
+        assert!(patch_set.to_yaml().unwrap().contains(
+            "addresses:\n      - 10.0.0.1/24\n      - 10.0.0.3/24\n      - 10.0.0.4/24\n"
+        ));
+        /* netplan appends sequences, the whole one has to be nulled first */
+        assert_eq!(
+            patch_set.to_nullable_yaml().unwrap(),
+            "network:
+  ethernets:
+    eth1: null
+    eth0:
+      dhcp4: null
+      addresses: null
+"
+        );
+
+        for invalid in [
+            r#"[{"op": "test", "path": "/network/ethernets/eth1/mtu", "value": 9000}]"#,
+            r#"[{"op": "remove", "path": "/network/ethernets/eth3"}]"#,
+            r#"[{"op": "add", "path": "/network/ethernets/eth3/mtu", "value": 9000}]"#,
+            r#"[{"op": "replace", "path": "/network/ethernets/eth0/addresses/01", "value": "x"}]"#,
+            r#"[{"op": "add", "path": "/other", "value": 1}]"#,
+            r#"[{"op": "remove", "path": ""}]"#,
+            r#"[{"op": "frobnicate", "path": "/network"}]"#,
+        ] {
+            assert!(
+                PatchSet::from_json_patch_document(invalid, document.clone()).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_patch_set_from_json_patch_state() {
+        let mut parser = crate::parser::Parser::new();
+        parser
+            .load_yaml_from_string(
+                "network:\n  ethernets:\n    eth0:\n      addresses: [10.0.0.1/24, 10.0.0.2/24]\n",
+            )
+            .unwrap();
+        let state = State::try_from(parser).unwrap();
+
+        let patch_set = PatchSet::from_json_patch(
+            r#"[{"op": "replace", "path": "/network/ethernets/eth0/addresses/1", "value": "10.0.0.3/24"}]"#,
+            &state,
+        )
+        .unwrap();
+
+        assert_eq!(patch_set.len(), 1);
+        assert_eq!(
+            patch_set.operations()[0].key_path().to_string(),
+            "network.ethernets.eth0.addresses"
+        );
+        assert!(patch_set
+            .to_yaml()
+            .unwrap()
+            .contains("addresses:\n      - 10.0.0.1/24\n      - 10.0.0.3/24\n"));
+
+        assert!(PatchSet::from_json_patch(
+            r#"[{"op": "remove", "path": "/network/ethernets/eth1"}]"#,
+            &state
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_patch_set_invalid() {
+        let mut patch_set = PatchSet::new();
+
+        assert!(patch_set.set("network", "{}").is_err());
+        assert!(patch_set
+            .set("ethernets.eth0.mtu", "[unterminated")
+            .is_err());
+        assert!(patch_set.unset("ethernets..eth0").is_err());
+        assert!(patch_set.is_empty());
+    }
+}
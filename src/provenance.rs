@@ -0,0 +1,373 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value;
+
+use crate::error::{ErrorCode, ErrorDomain, NetplanError, NetplanResult};
+use crate::hierarchy::{HierarchyLayer, VirtualHierarchy};
+use crate::parser::hierarchy_layer_files;
+
+/* The only mapping under "network" that isn't a section of netdefs */
+const GLOBAL_OPENVSWITCH: &str = "openvswitch";
+
+/// A file of the hierarchy defining, or nulling, a netdef.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    pub layer: HierarchyLayer,
+    pub path: PathBuf,
+    /// Whether a file with the same name in a higher layer hides this one,
+    /// in which case netplan never loads it.
+    pub shadowed: bool,
+    /// Whether the file sets the netdef to null, removing what lower
+    /// precedence files defined.
+    pub nulled: bool,
+    /// The top-level keys the file sets on the netdef, e.g. "dhcp4".
+    pub keys: Vec<String>,
+    /// The top-level keys the file sets to null, removing what lower
+    /// precedence files set.
+    pub nulled_keys: Vec<String>,
+}
+
+/// Where a netdef comes from: every file of the hierarchy mentioning it, in
+/// loading order. Shadowed files come right before the file shadowing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetdefProvenance {
+    pub id: String,
+    /// The YAML section the netdef is defined in, e.g. "ethernets".
+    pub section: String,
+    pub contributions: Vec<Contribution>,
+}
+
+impl NetdefProvenance {
+    /// The contributions netplan actually loads.
+    pub fn effective(&self) -> impl Iterator<Item = &Contribution> {
+        self.contributions
+            .iter()
+            .filter(|contribution| !contribution.shadowed)
+    }
+
+    /// The last file loaded for the netdef, i.e. the one
+    /// netplan_netdef_get_filepath() reports.
+    pub fn winner(&self) -> Option<&Contribution> {
+        self.effective().last()
+    }
+
+    /// The file the effective value of `key` comes from. None if the key
+    /// isn't set, or was nulled afterwards.
+    pub fn key_winner(&self, key: &str) -> Option<&Contribution> {
+        let nulls = |contribution: &&Contribution| {
+            contribution.nulled || contribution.nulled_keys.iter().any(|k| k == key)
+        };
+
+        self.effective()
+            .filter(|contribution| {
+                nulls(contribution) || contribution.keys.iter().any(|k| k == key)
+            })
+            .last()
+            .filter(|contribution| !nulls(contribution))
+    }
+
+    /// Every key set on the netdef by the loaded files and not nulled
+    /// afterwards, sorted.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .effective()
+            .flat_map(|contribution| contribution.keys.iter().map(|key| key.as_str()))
+            .filter(|key| self.key_winner(key).is_some())
+            .collect();
+
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+}
+
+/// The provenance of every netdef of a hierarchy, ordered by ID.
+///
+/// libnetplan doesn't record which files contribute to a netdef, only the
+/// last one, so the files are read here, following netplan's loading
+/// order. This has limits: the files are parsed as YAML 1.2, with aliases
+/// and `<<` merge keys resolved, while libnetplan reads YAML 1.1. Unquoted
+/// keys such as `on` or `yes` are taken as strings either way, but other
+/// YAML 1.1 only constructs may be read differently than netplan does.
+/// Only top-level keys of a netdef are tracked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    netdefs: Vec<NetdefProvenance>,
+}
+
+impl Provenance {
+    /// Reads the YAML files of the hierarchy at `root_dir`.
+    pub fn from_hierarchy(root_dir: impl AsRef<Path>) -> NetplanResult<Self> {
+        let root_dir = root_dir.as_ref();
+        let files = hierarchy_layer_files(root_dir)
+            .map_err(|e| NetplanError::io(&root_dir.display().to_string(), &e))?;
+
+        let mut provenance = Provenance::default();
+
+        for (index, (layer, name, path)) in files.iter().enumerate() {
+            let shadowed = files
+                .get(index + 1)
+                .is_some_and(|(_, next, _)| next == name);
+            let yaml =
+                fs::read(path).map_err(|e| NetplanError::io(&path.display().to_string(), &e))?;
+            provenance.add_file(*layer, path, &yaml, shadowed)?;
+        }
+
+        Ok(provenance)
+    }
+
+    /// Same as from_hierarchy(), for a hierarchy held in memory. Paths are
+    /// relative to the root directory, e.g. "etc/netplan/10-config.yaml".
+    pub fn from_virtual_hierarchy(hierarchy: &VirtualHierarchy) -> NetplanResult<Self> {
+        let mut files: Vec<_> = hierarchy.iter().collect();
+        files.sort_by(|a, b| (a.filename, a.layer).cmp(&(b.filename, b.layer)));
+
+        let mut provenance = Provenance::default();
+
+        for (index, file) in files.iter().enumerate() {
+            let shadowed = files
+                .get(index + 1)
+                .is_some_and(|next| next.filename == file.filename);
+            provenance.add_file(file.layer, &file.path(), file.yaml, shadowed)?;
+        }
+
+        Ok(provenance)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&NetdefProvenance> {
+        self.netdefs.iter().find(|netdef| netdef.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NetdefProvenance> {
+        self.netdefs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.netdefs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.netdefs.is_empty()
+    }
+
+    fn add_file(
+        &mut self,
+        layer: HierarchyLayer,
+        path: &Path,
+        yaml: &[u8],
+        shadowed: bool,
+    ) -> NetplanResult<()> {
+        let invalid_yaml = |e: serde_yaml::Error| {
+            let location = e
+                .location()
+                .map(|l| format!("{}:{}:{}: ", path.display(), l.line(), l.column()))
+                .unwrap_or_else(|| format!("{}: ", path.display()));
+            NetplanError::new(
+                ErrorDomain::Parser,
+                ErrorCode::InvalidYaml,
+                &format!("{location}{e}"),
+            )
+        };
+
+        let mut document: Value = serde_yaml::from_slice(yaml).map_err(invalid_yaml)?;
+        document.apply_merge().map_err(invalid_yaml)?;
+
+        let sections = match document.get("network").and_then(Value::as_mapping) {
+            Some(sections) => sections,
+            None => return Ok(()),
+        };
+
+        for (section, netdefs) in sections {
+            let (section, netdefs) = match (section.as_str(), netdefs.as_mapping()) {
+                /* Global settings, not netdefs */
+                (Some(GLOBAL_OPENVSWITCH), _) => continue,
+                (Some(section), Some(netdefs)) => (section, netdefs),
+                _ => continue,
+            };
+
+            for (id, settings) in netdefs {
+                let id = match id.as_str() {
+                    Some(id) => id,
+                    None => continue,
+                };
+
+                let mut keys = Vec::new();
+                let mut nulled_keys = Vec::new();
+
+                for (key, value) in settings.as_mapping().into_iter().flatten() {
+                    let Some(key) = key.as_str() else {
+                        continue;
+                    };
+
+                    if value.is_null() {
+                        nulled_keys.push(key.to_string());
+                    } else {
+                        keys.push(key.to_string());
+                    }
+                }
+
+                let contribution = Contribution {
+                    layer,
+                    path: path.to_path_buf(),
+                    shadowed,
+                    nulled: settings.is_null(),
+                    keys,
+                    nulled_keys,
+                };
+
+                match self.netdefs.iter_mut().find(|netdef| netdef.id == id) {
+                    Some(netdef) => netdef.contributions.push(contribution),
+                    None => self.netdefs.push(NetdefProvenance {
+                        id: id.to_string(),
+                        section: section.to_string(),
+                        contributions: vec![contribution],
+                    }),
+                }
+            }
+        }
+
+        self.netdefs.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(())
+    }
+}
+
+/* Loading order, with the files shadowed by a same-named file right before
+   it: by name, then by layer.
+*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::DirBuilder;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_provenance_virtual_hierarchy() {
+        let mut hierarchy = VirtualHierarchy::new();
+        hierarchy
+            .insert(
+                HierarchyLayer::Lib,
+                "50-cloud-init.yaml",
+                "network:\n  ethernets:\n    eth0:\n      dhcp4: true\n      mtu: 1500\n",
+            )
+            .unwrap();
+        hierarchy
+            .insert(
+                HierarchyLayer::Etc,
+                "50-cloud-init.yaml",
+                "network:\n  ethernets:\n    eth0:\n      dhcp4: false\n",
+            )
+            .unwrap();
+        hierarchy
+            .insert(
+                HierarchyLayer::Etc,
+                "90-admin.yaml",
+                "network:\n  ethernets:\n    eth0:\n      addresses: [10.0.0.1/24]\n    eth1: {}\n",
+            )
+            .unwrap();
+        hierarchy
+            .insert(
+                HierarchyLayer::Run,
+                "99-installer.yaml",
+                "network:\n  ethernets:\n    eth1: null\n    eth0:\n      addresses: null\n",
+            )
+            .unwrap();
+
+        hierarchy
+            .insert(
+                HierarchyLayer::Etc,
+                "95-merge.yaml",
+                "network:\n  ethernets:\n    eth2: &base\n      mtu: 9000\n    eth3:\n      <<: *base\n      dhcp4: true\n",
+            )
+            .unwrap();
+
+        let provenance = Provenance::from_virtual_hierarchy(&hierarchy).unwrap();
+        assert_eq!(provenance.len(), 4);
+
+        let eth0 = provenance.get("eth0").unwrap();
+        assert_eq!(eth0.section, "ethernets");
+        let paths: Vec<(&Path, bool)> = eth0
+            .contributions
+            .iter()
+            .map(|c| (c.path.as_path(), c.shadowed))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                (Path::new("lib/netplan/50-cloud-init.yaml"), true),
+                (Path::new("etc/netplan/50-cloud-init.yaml"), false),
+                (Path::new("etc/netplan/90-admin.yaml"), false),
+                (Path::new("run/netplan/99-installer.yaml"), false),
+            ]
+        );
+        assert_eq!(
+            eth0.winner().unwrap().path,
+            Path::new("run/netplan/99-installer.yaml")
+        );
+        assert_eq!(
+            eth0.key_winner("dhcp4").unwrap().path,
+            Path::new("etc/netplan/50-cloud-init.yaml")
+        );
+        assert_eq!(eth0.key_winner("mtu"), None);
+        assert_eq!(eth0.key_winner("addresses"), None);
+        assert_eq!(eth0.keys(), vec!["dhcp4"]);
+
+        let eth1 = provenance.get("eth1").unwrap();
+        assert!(eth1.winner().unwrap().nulled);
+        assert_eq!(eth1.winner().unwrap().layer, HierarchyLayer::Run);
+
+        /* Merge keys are resolved like libnetplan does */
+        assert_eq!(provenance.get("eth3").unwrap().keys(), vec!["dhcp4", "mtu"]);
+    }
+
+    #[test]
+    fn test_provenance_hierarchy() {
+        let root_dir = tempdir().unwrap();
+        for layer in HierarchyLayer::ALL {
+            DirBuilder::new()
+                .recursive(true)
+                .create(root_dir.path().join(layer.dir()))
+                .unwrap();
+        }
+        fs::write(
+            root_dir.path().join("lib/netplan/10-config.yaml"),
+            "network:\n  bonds:\n    bond0:\n      interfaces: [eth0]\n",
+        )
+        .unwrap();
+        fs::write(
+            root_dir.path().join("run/netplan/10-config.yaml"),
+            "network:\n  bonds:\n    bond0:\n      interfaces: [eth1]\n",
+        )
+        .unwrap();
+        fs::write(
+            root_dir.path().join("etc/netplan/20-bad.yaml"),
+            "network: [",
+        )
+        .unwrap();
+        /* libnetplan never loads it */
+        fs::write(
+            root_dir.path().join("etc/netplan/.hidden.yaml"),
+            "network:\n  bonds:\n    bond0:\n      interfaces: [eth2]\n",
+        )
+        .unwrap();
+
+        let error = Provenance::from_hierarchy(root_dir.path()).unwrap_err();
+        assert_eq!(error.domain, ErrorDomain::Parser);
+        assert_eq!(
+            error.file,
+            Some(root_dir.path().join("etc/netplan/20-bad.yaml"))
+        );
+
+        fs::remove_file(root_dir.path().join("etc/netplan/20-bad.yaml")).unwrap();
+        let provenance = Provenance::from_hierarchy(root_dir.path()).unwrap();
+        let bond0 = provenance.get("bond0").unwrap();
+        assert_eq!(bond0.section, "bonds");
+        assert_eq!(bond0.contributions.len(), 2);
+        assert!(bond0.contributions[0].shadowed);
+        assert_eq!(
+            bond0.winner().unwrap().path,
+            root_dir.path().join("run/netplan/10-config.yaml")
+        );
+    }
+}
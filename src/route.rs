@@ -0,0 +1,280 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::libnetplan::_netplan_netdef_new_route_iter;
+use crate::libnetplan::_netplan_route_iter_free;
+use crate::libnetplan::_netplan_route_iter_next;
+use crate::libnetplan::copy_raw_string;
+use crate::libnetplan::route_iter;
+use crate::libnetplan::NetplanIPRoute;
+use crate::libnetplan::NetplanNetDefinition;
+
+/* Sentinels used by libnetplan for route fields that weren't set */
+const NETPLAN_ROUTE_TABLE_UNSPEC: u32 = 0;
+const NETPLAN_METRIC_UNSPEC: u32 = u32::MAX;
+const AF_INET6: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteType {
+    Unicast,
+    Anycast,
+    Blackhole,
+    Broadcast,
+    Local,
+    Multicast,
+    Nat,
+    Prohibit,
+    Throw,
+    Unreachable,
+    Xresolve,
+}
+
+impl FromStr for RouteType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unicast" => Ok(RouteType::Unicast),
+            "anycast" => Ok(RouteType::Anycast),
+            "blackhole" => Ok(RouteType::Blackhole),
+            "broadcast" => Ok(RouteType::Broadcast),
+            "local" => Ok(RouteType::Local),
+            "multicast" => Ok(RouteType::Multicast),
+            "nat" => Ok(RouteType::Nat),
+            "prohibit" => Ok(RouteType::Prohibit),
+            "throw" => Ok(RouteType::Throw),
+            "unreachable" => Ok(RouteType::Unreachable),
+            "xresolve" => Ok(RouteType::Xresolve),
+            _ => Err(format!("invalid route type '{s}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteScope {
+    Global,
+    Link,
+    Host,
+}
+
+impl FromStr for RouteScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(RouteScope::Global),
+            "link" => Ok(RouteScope::Link),
+            "host" => Ok(RouteScope::Host),
+            _ => Err(format!("invalid route scope '{s}'")),
+        }
+    }
+}
+
+/// A network prefix such as a route destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpPrefix {
+    pub address: IpAddr,
+    pub prefix_length: u8,
+}
+
+impl IpPrefix {
+    pub fn is_default(&self) -> bool {
+        self.prefix_length == 0
+    }
+}
+
+impl FromStr for IpPrefix {
+    type Err = String;
+
+    /// Parses "address/prefix_length". A bare address is a host prefix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_length) = match s.split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (s, None),
+        };
+
+        let address =
+            IpAddr::from_str(address).map_err(|e| format!("invalid prefix '{s}': {e}"))?;
+
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => u8::from_str(prefix_length)
+                .map_err(|e| format!("invalid prefix length in '{s}': {e}"))?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+
+        Ok(IpPrefix {
+            address,
+            prefix_length,
+        })
+    }
+}
+
+/// A static route, copied from libnetplan's NetplanIPRoute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub r#type: RouteType,
+    pub scope: RouteScope,
+    pub table: Option<u32>,
+    pub from: Option<IpAddr>,
+    pub to: IpPrefix,
+    pub via: Option<IpAddr>,
+    pub on_link: bool,
+    pub metric: Option<u32>,
+    pub mtu: Option<u32>,
+    pub congestion_window: Option<u32>,
+    pub advertised_receive_window: Option<u32>,
+}
+
+impl Route {
+    fn from_raw_route(route: &NetplanIPRoute) -> Result<Self, String> {
+        let r#type = match copy_raw_string(route.type_) {
+            Some(r#type) => RouteType::from_str(&r#type)?,
+            None => RouteType::Unicast,
+        };
+
+        let scope = match copy_raw_string(route.scope) {
+            Some(scope) => RouteScope::from_str(&scope)?,
+            None => RouteScope::Global,
+        };
+
+        let to = copy_raw_string(route.to).ok_or("route has no destination".to_string())?;
+        let to = if to == "default" {
+            let address = if route.family == AF_INET6 {
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+            } else {
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+            };
+            IpPrefix {
+                address,
+                prefix_length: 0,
+            }
+        } else {
+            IpPrefix::from_str(&to)?
+        };
+
+        let from = copy_raw_string(route.from)
+            .map(|from| IpAddr::from_str(&from).map_err(|e| format!("invalid from '{from}': {e}")))
+            .transpose()?;
+
+        let via = copy_raw_string(route.via)
+            .map(|via| IpAddr::from_str(&via).map_err(|e| format!("invalid via '{via}': {e}")))
+            .transpose()?;
+
+        Ok(Route {
+            r#type,
+            scope,
+            table: (route.table != NETPLAN_ROUTE_TABLE_UNSPEC).then_some(route.table),
+            from,
+            to,
+            via,
+            on_link: route.onlink != 0,
+            metric: (route.metric != NETPLAN_METRIC_UNSPEC).then_some(route.metric),
+            mtu: (route.mtubytes != 0).then_some(route.mtubytes),
+            congestion_window: (route.congestion_window != 0).then_some(route.congestion_window),
+            advertised_receive_window: (route.advertised_receive_window != 0)
+                .then_some(route.advertised_receive_window),
+        })
+    }
+}
+
+pub(crate) struct RouteIter {
+    iter: *mut route_iter,
+}
+
+impl RouteIter {
+    pub(crate) fn new(netdef: *const NetplanNetDefinition) -> Self {
+        RouteIter {
+            iter: unsafe { _netplan_netdef_new_route_iter(netdef as *mut NetplanNetDefinition) },
+        }
+    }
+}
+
+impl Iterator for RouteIter {
+    type Item = Result<Route, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let route = unsafe { _netplan_route_iter_next(self.iter) };
+
+        if route.is_null() {
+            return None;
+        }
+
+        Some(Route::from_raw_route(unsafe { &*route }))
+    }
+}
+
+impl Drop for RouteIter {
+    fn drop(&mut self) {
+        unsafe { _netplan_route_iter_free(self.iter) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::state::State;
+
+    #[test]
+    fn test_ip_prefix_from_str() {
+        let prefix = IpPrefix::from_str("10.0.0.0/8").unwrap();
+        assert_eq!(prefix.address, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)));
+        assert_eq!(prefix.prefix_length, 8);
+
+        let prefix = IpPrefix::from_str("2001:db8::1").unwrap();
+        assert_eq!(prefix.prefix_length, 128);
+
+        assert!(IpPrefix::from_str("default").is_err());
+    }
+
+    #[test]
+    fn test_netdef_routes() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      addresses:
+        - 192.168.0.1/24
+      routes:
+        - to: default
+          via: 192.168.0.254
+        - to: 10.0.0.0/8
+          via: 192.168.0.253
+          metric: 100
+          table: 200
+          on-link: true
+          mtu: 1400
+        - to: 172.16.0.0/12
+          type: blackhole";
+
+        let mut parser = Parser::new();
+        parser.load_yaml_from_string(yaml).unwrap();
+        let state = State::try_from(parser).unwrap();
+
+        let netdef = state.iter().next().unwrap();
+        let routes: Vec<Route> = netdef.routes().map(Result::unwrap).collect();
+
+        assert_eq!(routes.len(), 3);
+
+        assert!(routes[0].to.is_default());
+        assert_eq!(routes[0].to.address, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(
+            routes[0].via,
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 254)))
+        );
+        assert_eq!(routes[0].r#type, RouteType::Unicast);
+        assert_eq!(routes[0].scope, RouteScope::Global);
+        assert_eq!(routes[0].metric, None);
+        assert_eq!(routes[0].table, None);
+
+        assert_eq!(routes[1].to, IpPrefix::from_str("10.0.0.0/8").unwrap());
+        assert_eq!(routes[1].metric, Some(100));
+        assert_eq!(routes[1].table, Some(200));
+        assert_eq!(routes[1].mtu, Some(1400));
+        assert!(routes[1].on_link);
+
+        assert_eq!(routes[2].r#type, RouteType::Blackhole);
+        assert_eq!(routes[2].via, None);
+    }
+}
@@ -0,0 +1,708 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::error::NetplanError;
+use crate::error::NetplanResult;
+use crate::interface::InterfaceInfo;
+use crate::libnetplan::state_get_vf_count_for_def;
+use crate::state::State;
+
+/* The SR-IOV specification limits a PF to 256 VFs */
+const SRIOV_MAX_VFS: u32 = 256;
+/* EBUSY */
+const DEVICE_BUSY: i32 = 16;
+
+/// The mode of the embedded switch of a SR-IOV capable NIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EswitchMode {
+    Legacy,
+    Switchdev,
+}
+
+impl EswitchMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EswitchMode::Legacy => "legacy",
+            EswitchMode::Switchdev => "switchdev",
+        }
+    }
+}
+
+impl FromStr for EswitchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(EswitchMode::Legacy),
+            "switchdev" => Ok(EswitchMode::Switchdev),
+            _ => Err(format!("invalid embedded switch mode '{s}'")),
+        }
+    }
+}
+
+/// A VLAN filtered in hardware by one of the VFs of a PF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VlanFilter {
+    /// The ID of the VLAN netdef.
+    pub vlan: String,
+    pub vlan_id: u32,
+    /// The ID of the VF netdef the VLAN links to.
+    pub vf: String,
+}
+
+/// What has to be configured on a physical function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PfPlan {
+    /// The ID of the PF netdef.
+    pub pf: String,
+    /// The number of VFs to allocate, either the explicit
+    /// virtual-function-count or the number of VF netdefs.
+    pub vf_count: u32,
+    /// The IDs of the netdefs linking to the PF, in State order.
+    pub vfs: Vec<String>,
+    pub eswitch_mode: Option<EswitchMode>,
+    /// Leave the VFs unbound after switching the eswitch mode.
+    pub delay_vf_rebind: bool,
+    pub vlan_filters: Vec<VlanFilter>,
+}
+
+/// The SR-IOV configuration of a State, one entry per PF.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SriovPlan {
+    pub pfs: Vec<PfPlan>,
+}
+
+impl SriovPlan {
+    pub fn get(&self, pf: &str) -> Option<&PfPlan> {
+        self.pfs.iter().find(|plan| plan.pf == pf)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pfs.is_empty()
+    }
+}
+
+impl TryFrom<&State> for SriovPlan {
+    type Error = NetplanError;
+
+    /* A netdef is a PF if some netdef links to it with `link`, if it sets
+       virtual-function-count or if it sets embedded-switch-mode.
+    */
+    fn try_from(state: &State) -> NetplanResult<Self> {
+        let mut pfs: Vec<PfPlan> = Vec::new();
+
+        for netdef in state.ethernets() {
+            let vf_count = state_get_vf_count_for_def(state.state, netdef.as_ptr())?;
+            let eswitch_mode = netdef
+                .embedded_switch_mode()?
+                .map(|mode| EswitchMode::from_str(&mode))
+                .transpose()
+                .map_err(|e| NetplanError::validation(&e))?;
+
+            if vf_count == 0 && eswitch_mode.is_none() {
+                continue;
+            }
+
+            let id = netdef.id()?;
+            let mut vfs = Vec::new();
+            for vf in state.ethernets() {
+                if vf.sriov_link() == Some(netdef) {
+                    vfs.push(vf.id()?);
+                }
+            }
+
+            pfs.push(PfPlan {
+                pf: id,
+                vf_count,
+                vfs,
+                eswitch_mode,
+                delay_vf_rebind: netdef.delay_virtual_functions_rebind(),
+                vlan_filters: Vec::new(),
+            });
+        }
+
+        for vlan in state.vlans().filter(|vlan| vlan.sriov_vlan_filter()) {
+            let vlan_id = vlan.id()?;
+
+            let vf = vlan.vlan_link().ok_or_else(|| {
+                NetplanError::validation(&format!("{vlan_id}: SR-IOV VLAN filter requires a link"))
+            })?;
+            let vf_id = vf.id()?;
+
+            let pf = vf.sriov_link().ok_or_else(|| {
+                NetplanError::validation(&format!(
+                    "{vlan_id}: SR-IOV VLAN filter defined on non-VF interface {vf_id}"
+                ))
+            })?;
+            let pf_id = pf.id()?;
+
+            let plan = pfs
+                .iter_mut()
+                .find(|plan| plan.pf == pf_id)
+                .ok_or_else(|| {
+                    NetplanError::validation(&format!(
+                        "{vlan_id}: PF {pf_id} of VF {vf_id} has no VFs to allocate"
+                    ))
+                })?;
+
+            let filter_id = vlan.vlan_id().ok_or_else(|| {
+                NetplanError::validation(&format!(
+                    "{vlan_id}: SR-IOV VLAN filter requires a VLAN id"
+                ))
+            })?;
+
+            plan.vlan_filters.push(VlanFilter {
+                vlan: vlan_id,
+                vlan_id: filter_id,
+                vf: vf_id,
+            });
+        }
+
+        Ok(SriovPlan { pfs })
+    }
+}
+
+/// An action taken, or deliberately skipped, while applying a SriovPlan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SriovStep {
+    /// `sriov_numvfs` of the PF interface was set to `count`.
+    SetNumVfs { interface: String, count: u32 },
+    /// The PF interface already had `current` VFs, enough for the `count`
+    /// required, so it was left alone.
+    KeepNumVfs {
+        interface: String,
+        current: u32,
+        count: u32,
+    },
+    /// The VF at `pci_address` was unbound from `driver`.
+    UnbindVf { pci_address: String, driver: String },
+    /// The embedded switch of the PF at `pci_address` was switched to `mode`.
+    SetEswitchMode {
+        pci_address: String,
+        mode: EswitchMode,
+    },
+    /// The VF at `pci_address` was bound back to `driver`.
+    BindVf { pci_address: String, driver: String },
+}
+
+/// Configures the SR-IOV PFs of `state` through the sysfs tree mounted at
+/// `sysfs_root`, the way `netplan apply` does: allocate the VFs, then switch
+/// the eswitch mode with devlink, unbinding the VFs around the switch.
+pub fn apply(state: &State, sysfs_root: impl AsRef<Path>) -> NetplanResult<Vec<SriovStep>> {
+    apply_with(state, sysfs_root, devlink_set_eswitch_mode)
+}
+
+/// Same as apply(), but the eswitch mode is switched by calling
+/// `set_eswitch_mode` with the PCI address of the PF.
+pub fn apply_with(
+    state: &State,
+    sysfs_root: impl AsRef<Path>,
+    mut set_eswitch_mode: impl FnMut(&str, EswitchMode) -> io::Result<()>,
+) -> NetplanResult<Vec<SriovStep>> {
+    let sysfs = Sysfs::new(sysfs_root.as_ref());
+    let plan = SriovPlan::try_from(state)?;
+    let interfaces = sysfs.interfaces()?;
+    let mut steps = Vec::new();
+
+    let mut pf_interfaces = Vec::new();
+    for pf in &plan.pfs {
+        let interface = find_pf_interface(state, &pf.pf, &interfaces)?;
+        pf_interfaces.push((pf, interface));
+    }
+
+    for (pf, interface) in &pf_interfaces {
+        if pf.vf_count > 0 {
+            steps.push(sysfs.set_numvfs(interface, pf.vf_count)?);
+        }
+    }
+
+    for (pf, interface) in &pf_interfaces {
+        let mode = match pf.eswitch_mode {
+            Some(mode) => mode,
+            None => continue,
+        };
+
+        /* Only PFs have a sriov_numvfs attribute */
+        if !sysfs.device_dir(interface).join("sriov_numvfs").exists() {
+            continue;
+        }
+
+        let vfs = sysfs.vf_addresses(interface)?;
+        if vfs.is_empty() {
+            continue;
+        }
+
+        let pci_address = sysfs.pci_address(interface)?;
+        let driver = sysfs.driver(&sysfs.device_dir(interface))?;
+        let mut unbound = Vec::new();
+
+        for vf in vfs {
+            if !sysfs.pci_device_dir(&vf).join("driver").exists() {
+                continue;
+            }
+            sysfs.write_driver_attribute(&driver, "unbind", &vf)?;
+            steps.push(SriovStep::UnbindVf {
+                pci_address: vf.clone(),
+                driver: driver.clone(),
+            });
+            unbound.push(vf);
+        }
+
+        set_eswitch_mode(&pci_address, mode).map_err(|e| {
+            NetplanError::io(
+                &format!(
+                    "failed setting eswitch mode {} for {interface}",
+                    mode.name()
+                ),
+                &e,
+            )
+        })?;
+        steps.push(SriovStep::SetEswitchMode { pci_address, mode });
+
+        if pf.delay_vf_rebind {
+            continue;
+        }
+
+        for vf in unbound {
+            sysfs.write_driver_attribute(&driver, "bind", &vf)?;
+            steps.push(SriovStep::BindVf {
+                pci_address: vf,
+                driver: driver.clone(),
+            });
+        }
+    }
+
+    Ok(steps)
+}
+
+fn devlink_set_eswitch_mode(pci_address: &str, mode: EswitchMode) -> io::Result<()> {
+    let status = Command::new("devlink")
+        .args(["dev", "eswitch", "set"])
+        .arg(format!("pci/{pci_address}"))
+        .args(["mode", mode.name()])
+        .status()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!("devlink exited with {status}")));
+    }
+
+    Ok(())
+}
+
+/* A PF with a match stanza is the first interface matching it, otherwise
+   the interface named after the netdef ID.
+*/
+fn find_pf_interface(
+    state: &State,
+    pf: &str,
+    interfaces: &[InterfaceInfo],
+) -> NetplanResult<String> {
+    let netdef = state
+        .get_ref(pf)
+        .ok_or_else(|| NetplanError::validation(&format!("{pf}: netdef not found")))?;
+
+    interfaces
+        .iter()
+        .find(|interface| netdef.matches(interface))
+        .map(|interface| interface.name.clone())
+        .ok_or_else(|| {
+            NetplanError::validation(&format!("{pf}: no interface found for the SR-IOV PF"))
+        })
+}
+
+struct Sysfs {
+    root: PathBuf,
+}
+
+impl Sysfs {
+    fn new(root: &Path) -> Self {
+        Sysfs {
+            root: root.to_path_buf(),
+        }
+    }
+
+    fn file_error(path: &Path, error: io::Error) -> NetplanError {
+        NetplanError::io(&path.display().to_string(), &error)
+    }
+
+    fn device_dir(&self, interface: &str) -> PathBuf {
+        self.root.join("class/net").join(interface).join("device")
+    }
+
+    fn pci_device_dir(&self, pci_address: &str) -> PathBuf {
+        self.root.join("bus/pci/devices").join(pci_address)
+    }
+
+    fn link_name(path: &Path) -> NetplanResult<String> {
+        let target = fs::read_link(path).map_err(|e| Sysfs::file_error(path, e))?;
+
+        target
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| {
+                NetplanError::generic(&format!("{}: invalid link target", path.display()))
+            })
+    }
+
+    fn driver(&self, device_dir: &Path) -> NetplanResult<String> {
+        Sysfs::link_name(&device_dir.join("driver"))
+    }
+
+    fn pci_address(&self, interface: &str) -> NetplanResult<String> {
+        Sysfs::link_name(&self.device_dir(interface))
+    }
+
+    /// The network interfaces with their MAC address and driver.
+    fn interfaces(&self) -> NetplanResult<Vec<InterfaceInfo>> {
+        let class_net = self.root.join("class/net");
+        let entries = fs::read_dir(&class_net).map_err(|e| Sysfs::file_error(&class_net, e))?;
+        let mut interfaces = Vec::new();
+
+        for entry in entries {
+            let entry = entry.map_err(|e| Sysfs::file_error(&class_net, e))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            let mac = fs::read_to_string(entry.path().join("address"))
+                .ok()
+                .map(|mac| mac.trim().to_string());
+            let driver = self.driver(&self.device_dir(&name)).ok();
+
+            interfaces.push(InterfaceInfo { name, mac, driver });
+        }
+
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(interfaces)
+    }
+
+    /// The PCI addresses of the VFs of a PF, from its virtfnN links.
+    fn vf_addresses(&self, interface: &str) -> NetplanResult<Vec<String>> {
+        let device_dir = self.device_dir(interface);
+        let entries = fs::read_dir(&device_dir).map_err(|e| Sysfs::file_error(&device_dir, e))?;
+        let mut vfs = Vec::new();
+
+        for entry in entries {
+            let entry = entry.map_err(|e| Sysfs::file_error(&device_dir, e))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if let Some(index) = name.strip_prefix("virtfn") {
+                if let Ok(index) = index.parse::<u32>() {
+                    vfs.push((index, Sysfs::link_name(&entry.path())?));
+                }
+            }
+        }
+
+        vfs.sort();
+
+        Ok(vfs.into_iter().map(|(_, vf)| vf).collect())
+    }
+
+    fn read_u32(path: &Path) -> NetplanResult<u32> {
+        let value = fs::read_to_string(path).map_err(|e| Sysfs::file_error(path, e))?;
+
+        value.trim().parse().map_err(|_| {
+            NetplanError::generic(&format!(
+                "{}: invalid value '{}'",
+                path.display(),
+                value.trim()
+            ))
+        })
+    }
+
+    fn write_driver_attribute(
+        &self,
+        driver: &str,
+        attribute: &str,
+        pci_address: &str,
+    ) -> NetplanResult<()> {
+        let path = self
+            .root
+            .join("bus/pci/drivers")
+            .join(driver)
+            .join(attribute);
+
+        fs::write(&path, pci_address).map_err(|e| Sysfs::file_error(&path, e))
+    }
+
+    /* Mirrors set_numvfs_for_pf() from netplan's CLI: VFs are never
+       removed, and a busy device is retried after resetting it to 0 VFs.
+    */
+    fn set_numvfs(&self, interface: &str, count: u32) -> NetplanResult<SriovStep> {
+        if count > SRIOV_MAX_VFS {
+            return Err(NetplanError::validation(&format!(
+                "cannot allocate more VFs for PF {interface} than the SR-IOV maximum: {count} > {SRIOV_MAX_VFS}"
+            )));
+        }
+
+        let device_dir = self.device_dir(interface);
+        let numvfs_path = device_dir.join("sriov_numvfs");
+        let current = Sysfs::read_u32(&numvfs_path)?;
+        let total = Sysfs::read_u32(&device_dir.join("sriov_totalvfs"))?;
+
+        if current >= count {
+            return Ok(SriovStep::KeepNumVfs {
+                interface: interface.to_string(),
+                current,
+                count,
+            });
+        }
+
+        if count > total {
+            return Err(NetplanError::validation(&format!(
+                "cannot allocate more VFs for PF {interface} than supported: {count} > {total} (sriov_totalvfs)"
+            )));
+        }
+
+        let result = match fs::write(&numvfs_path, count.to_string()) {
+            Err(e) if e.raw_os_error() == Some(DEVICE_BUSY) => fs::write(&numvfs_path, "0")
+                .and_then(|_| fs::write(&numvfs_path, count.to_string())),
+            result => result,
+        };
+
+        result.map_err(|e| Sysfs::file_error(&numvfs_path, e))?;
+
+        Ok(SriovStep::SetNumVfs {
+            interface: interface.to_string(),
+            count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorDomain;
+    use crate::parser::Parser;
+
+    fn create_state(yaml: &str) -> State {
+        let mut parser = Parser::new();
+        parser.load_yaml_from_string(yaml).unwrap();
+
+        State::try_from(parser).unwrap()
+    }
+
+    #[test]
+    fn test_sriov_plan() {
+        let yaml = r"
+network:
+  ethernets:
+    enp1:
+      embedded-switch-mode: switchdev
+      delay-virtual-functions-rebind: true
+    enp2:
+      virtual-function-count: 8
+    enp1s16f1:
+      link: enp1
+    enp1s16f2:
+      link: enp1
+    eth0: {}
+  vlans:
+    vlan10:
+      id: 10
+      link: enp1s16f1
+      renderer: sriov";
+
+        let state = create_state(yaml);
+        let plan = SriovPlan::try_from(&state).unwrap();
+
+        assert_eq!(plan.pfs.len(), 2);
+        assert!(plan.get("eth0").is_none());
+
+        let enp1 = plan.get("enp1").unwrap();
+        assert_eq!(enp1.vf_count, 2);
+        let mut vfs = enp1.vfs.clone();
+        vfs.sort();
+        assert_eq!(vfs, vec!["enp1s16f1", "enp1s16f2"]);
+        assert_eq!(enp1.eswitch_mode, Some(EswitchMode::Switchdev));
+        assert!(enp1.delay_vf_rebind);
+        assert_eq!(
+            enp1.vlan_filters,
+            vec![VlanFilter {
+                vlan: "vlan10".to_string(),
+                vlan_id: 10,
+                vf: "enp1s16f1".to_string(),
+            }]
+        );
+
+        let enp2 = plan.get("enp2").unwrap();
+        assert_eq!(enp2.vf_count, 8);
+        assert!(enp2.vfs.is_empty());
+        assert_eq!(enp2.eswitch_mode, None);
+        assert!(!enp2.delay_vf_rebind);
+    }
+
+    #[test]
+    fn test_sriov_plan_too_many_vfs() {
+        let yaml = r"
+network:
+  ethernets:
+    enp1:
+      virtual-function-count: 1
+    enp1s16f1:
+      link: enp1
+    enp1s16f2:
+      link: enp1";
+
+        let state = create_state(yaml);
+
+        match SriovPlan::try_from(&state) {
+            Err(error) => {
+                assert_eq!(error.domain, ErrorDomain::Validation);
+                assert!(error
+                    .message
+                    .contains("more VFs allocated than the explicit size declared"));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    fn create_sysfs_pf(root: &Path, interface: &str, pci_address: &str, vfs: &[&str]) {
+        use std::os::unix::fs::symlink;
+
+        let devices = root.join("bus/pci/devices");
+        let driver = root.join("bus/pci/drivers/mlx5_core");
+        fs::create_dir_all(&driver).unwrap();
+        fs::create_dir_all(root.join("class/net").join(interface)).unwrap();
+
+        let pf_dir = devices.join(pci_address);
+        fs::create_dir_all(&pf_dir).unwrap();
+        symlink(&driver, pf_dir.join("driver")).unwrap();
+        fs::write(pf_dir.join("sriov_numvfs"), "0\n").unwrap();
+        fs::write(pf_dir.join("sriov_totalvfs"), "8\n").unwrap();
+        symlink(
+            &pf_dir,
+            root.join("class/net").join(interface).join("device"),
+        )
+        .unwrap();
+
+        for (index, vf) in vfs.iter().enumerate() {
+            let vf_dir = devices.join(vf);
+            fs::create_dir_all(&vf_dir).unwrap();
+            symlink(&driver, vf_dir.join("driver")).unwrap();
+            symlink(&vf_dir, pf_dir.join(format!("virtfn{index}"))).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sriov_apply() {
+        let yaml = r"
+network:
+  ethernets:
+    enp1:
+      embedded-switch-mode: switchdev
+    enp1s16f1:
+      link: enp1
+    enp1s16f2:
+      link: enp1
+    pf2:
+      match:
+        name: enp2
+      virtual-function-count: 4
+      embedded-switch-mode: legacy
+      delay-virtual-functions-rebind: true";
+
+        let state = create_state(yaml);
+        let root = tempfile::tempdir().unwrap();
+        create_sysfs_pf(
+            root.path(),
+            "enp1",
+            "0000:01:00.0",
+            &["0000:01:00.2", "0000:01:00.3"],
+        );
+        create_sysfs_pf(root.path(), "enp2", "0000:02:00.0", &["0000:02:00.2"]);
+
+        let mut switched = Vec::new();
+        let steps = apply_with(&state, root.path(), |pci_address, mode| {
+            switched.push((pci_address.to_string(), mode));
+            Ok(())
+        })
+        .unwrap();
+
+        let numvfs = |pci_address: &str| {
+            fs::read_to_string(
+                root.path()
+                    .join("bus/pci/devices")
+                    .join(pci_address)
+                    .join("sriov_numvfs"),
+            )
+            .unwrap()
+        };
+        assert_eq!(numvfs("0000:01:00.0"), "2");
+        assert_eq!(numvfs("0000:02:00.0"), "4");
+
+        assert_eq!(
+            switched,
+            vec![
+                ("0000:01:00.0".to_string(), EswitchMode::Switchdev),
+                ("0000:02:00.0".to_string(), EswitchMode::Legacy),
+            ]
+        );
+
+        /* Every sysfs and devlink write, in the order it was made */
+        let unbind = |pci_address: &str| SriovStep::UnbindVf {
+            pci_address: pci_address.to_string(),
+            driver: "mlx5_core".to_string(),
+        };
+        let bind = |pci_address: &str| SriovStep::BindVf {
+            pci_address: pci_address.to_string(),
+            driver: "mlx5_core".to_string(),
+        };
+        assert_eq!(
+            steps,
+            vec![
+                SriovStep::SetNumVfs {
+                    interface: "enp1".to_string(),
+                    count: 2,
+                },
+                SriovStep::SetNumVfs {
+                    interface: "enp2".to_string(),
+                    count: 4,
+                },
+                unbind("0000:01:00.2"),
+                unbind("0000:01:00.3"),
+                SriovStep::SetEswitchMode {
+                    pci_address: "0000:01:00.0".to_string(),
+                    mode: EswitchMode::Switchdev,
+                },
+                bind("0000:01:00.2"),
+                bind("0000:01:00.3"),
+                unbind("0000:02:00.2"),
+                SriovStep::SetEswitchMode {
+                    pci_address: "0000:02:00.0".to_string(),
+                    mode: EswitchMode::Legacy,
+                },
+            ]
+        );
+
+        let driver = root.path().join("bus/pci/drivers/mlx5_core");
+        assert_eq!(
+            fs::read_to_string(driver.join("unbind")).unwrap(),
+            "0000:02:00.2"
+        );
+        assert_eq!(
+            fs::read_to_string(driver.join("bind")).unwrap(),
+            "0000:01:00.3"
+        );
+    }
+
+    #[test]
+    fn test_sriov_apply_too_many_vfs() {
+        let yaml = r"
+network:
+  ethernets:
+    enp1:
+      virtual-function-count: 16";
+
+        let state = create_state(yaml);
+        let root = tempfile::tempdir().unwrap();
+        create_sysfs_pf(root.path(), "enp1", "0000:01:00.0", &[]);
+
+        match apply_with(&state, root.path(), |_, _| Ok(())) {
+            Err(error) => assert!(error.message.contains("than supported: 16 > 8")),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
@@ -1,107 +1,114 @@
 use std::ffi::CString;
 use std::fs::File;
-use std::io::{prelude::*, Read, SeekFrom};
-use std::os::fd::FromRawFd;
+use std::marker::PhantomData;
 use std::os::unix::io::AsRawFd;
-use std::ptr::null_mut;
 
+use serde::de::DeserializeOwned;
+
+use crate::dns::Dns;
+use crate::error::NetplanError;
+use crate::error::NetplanResult;
+use crate::interface::InterfaceInfo;
+use crate::interface::InterfaceResolution;
+use crate::keypath::ToKeyPath;
+use crate::libnetplan::_netplan_netdef_pertype_iter_free;
 use crate::libnetplan::netdef_pertype_iter;
+use crate::libnetplan::netdef_type_raw_name;
+use crate::libnetplan::netplan_memfd_read;
 use crate::libnetplan::netplan_state_clear;
 use crate::libnetplan::netplan_state_dump_yaml;
+use crate::libnetplan::netplan_state_get_netdef;
+use crate::libnetplan::netplan_state_get_netdefs_size;
 use crate::libnetplan::netplan_state_import_parser_results;
 use crate::libnetplan::netplan_state_new;
-use crate::libnetplan::netplan_util_dump_yaml_subtree;
-use crate::libnetplan::NetplanError;
-use crate::libnetplan::NetplanErrorDomains;
-use crate::libnetplan::NetplanResult;
+use crate::libnetplan::netplan_state_write_yaml_file;
+use crate::libnetplan::state_get_backend;
+use crate::libnetplan::to_cstring;
+use crate::libnetplan::GError;
+use crate::libnetplan::NetplanNetDefinition;
 use crate::libnetplan::NetplanState;
 use crate::libnetplan::{_netplan_netdef_pertype_iter_next, netplan_memfd_create};
 use crate::libnetplan::{
     _netplan_state_new_netdef_pertype_iter, netplan_state_update_yaml_hierarchy,
 };
-use crate::libnetplan::{error_get_message, netplan_state_write_yaml_file};
+use crate::netdef::Backend;
 use crate::netdef::Netdef;
+use crate::netdef::NetdefRef;
+use crate::netdef::NetdefType;
 use crate::parser::Parser;
+use crate::sriov::SriovPlan;
+use crate::topology::Topology;
+use crate::utils::netplan_dump_yaml_subtree;
+use crate::value::Value;
 
 pub struct State {
     pub(crate) state: *mut NetplanState,
-    iter: *mut netdef_pertype_iter,
 }
 
 impl State {
     pub fn new() -> Self {
         State {
             state: unsafe { netplan_state_new() },
-            iter: ::std::ptr::null_mut(),
         }
     }
 
-    pub fn import_parser_state(&self, mut parser: Parser) -> NetplanResult<()> {
-        unsafe {
-            let mut error_message = ::std::ptr::null_mut::<NetplanError>();
-            let error = netplan_state_import_parser_results(
-                self.state,
-                parser.as_mut_ptr(),
-                &mut error_message,
-            );
-            if error == 0 {
-                if !error_message.is_null() {
-                    if let Some(message) = error_get_message(error_message) {
-                        return Err(NetplanErrorDomains::NetplanValidationError(message));
-                    } else {
-                        return Err(NetplanErrorDomains::NetplanValidationError(
-                            "import parser state error".to_string(),
-                        ));
-                    }
-                }
-            }
+    pub fn import_parser_state(&mut self, mut parser: Parser) -> NetplanResult<()> {
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
+
+        let ret = unsafe {
+            netplan_state_import_parser_results(self.state, parser.as_mut_ptr(), &mut netplan_error)
+        };
+
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "import_parser_state",
+            ));
         }
+
         Ok(())
     }
 
     pub fn dump_yaml(&self) -> NetplanResult<String> {
-        let mem_file = netplan_memfd_create().expect("Cannot create memory file");
-        unsafe {
-            netplan_state_dump_yaml(self.state, mem_file.as_raw_fd(), ::std::ptr::null_mut());
-        }
-        let mut file = unsafe { File::from_raw_fd(mem_file.as_raw_fd()) };
-        _ = file.seek(SeekFrom::Start(0));
-        let mut yaml = String::new();
-        file.read_to_string(&mut yaml)
-            .expect("Cannot read from memory file");
-        Ok(yaml)
-    }
-
-    pub fn dump_yaml_subtree(&self, subtree: &str) -> NetplanResult<String> {
-        let input_file = netplan_memfd_create().expect("Cannot create memory file");
-        let output_file = netplan_memfd_create().expect("Cannot create memory file");
-        unsafe {
-            netplan_state_dump_yaml(self.state, input_file.as_raw_fd(), ::std::ptr::null_mut());
-        }
-        let mut file = unsafe { File::from_raw_fd(input_file.as_raw_fd()) };
-        _ = file.seek(SeekFrom::Start(0));
+        let mut file = File::from(netplan_memfd_create()?);
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
-        let mut subtree_components: Vec<&str> = subtree.split('.').collect();
-        if subtree_components[0] != "network" {
-            subtree_components.insert(0, "network");
-        }
-        let subtree_string = CString::new(subtree_components.join("\t")).unwrap();
-
-        unsafe {
-            netplan_util_dump_yaml_subtree(
-                subtree_string.as_ptr(),
-                input_file.as_raw_fd(),
-                output_file.as_raw_fd(),
-                ::std::ptr::null_mut(),
-            );
+        let ret =
+            unsafe { netplan_state_dump_yaml(self.state, file.as_raw_fd(), &mut netplan_error) };
+
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(netplan_error, "dump_yaml"));
         }
 
-        file = unsafe { File::from_raw_fd(output_file.as_raw_fd()) };
-        _ = file.seek(SeekFrom::Start(0));
-        let mut yaml = String::new();
-        file.read_to_string(&mut yaml)
-            .expect("Cannot read from memory file");
-        Ok(yaml)
+        netplan_memfd_read(&mut file)
+    }
+
+    pub fn dump_yaml_subtree(&self, subtree: impl ToKeyPath) -> NetplanResult<String> {
+        netplan_dump_yaml_subtree(
+            &subtree.to_key_path()?.rooted(),
+            self.dump_yaml()?.as_bytes(),
+        )
+    }
+
+    /// The value at `key_path`, e.g. "ethernets.eth0.mtu", as
+    /// dump_yaml_subtree() would print it. Null if nothing is set there.
+    pub fn get_path(&self, key_path: impl ToKeyPath) -> NetplanResult<Value> {
+        let key_path = key_path.to_key_path()?;
+        let yaml = self.dump_yaml_subtree(&key_path)?;
+
+        serde_yaml::from_str::<serde_yaml::Value>(&yaml)
+            .map(Value::from)
+            .map_err(|e| NetplanError::generic(&format!("cannot parse {key_path}: {e}")))
+    }
+
+    /// Same as get_path(), deserializing the value into `T`, e.g. a u32
+    /// for "ethernets.eth0.mtu" or a user struct for "ethernets.eth0".
+    pub fn get_path_as<T: DeserializeOwned>(&self, key_path: impl ToKeyPath) -> NetplanResult<T> {
+        let key_path = key_path.to_key_path()?;
+        let yaml = self.dump_yaml_subtree(&key_path)?;
+
+        serde_yaml::from_str::<T>(&yaml)
+            .map_err(|e| NetplanError::generic(&format!("cannot deserialize {key_path}: {e}")))
     }
 
     pub fn update_yaml_hierarchy(
@@ -109,72 +116,223 @@ impl State {
         default_filename: &str,
         root_dir: &str,
     ) -> NetplanResult<()> {
-        let default_filename_cstr = CString::new(default_filename).unwrap();
-        let root_dir_cstr = CString::new(root_dir).unwrap();
+        let default_filename_cstr = to_cstring(default_filename)?;
+        let root_dir_cstr = to_cstring(root_dir)?;
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
-        unsafe {
-            let ret = netplan_state_update_yaml_hierarchy(
+        let ret = unsafe {
+            netplan_state_update_yaml_hierarchy(
                 self.state,
                 default_filename_cstr.as_ptr(),
                 root_dir_cstr.as_ptr(),
-                null_mut(),
-            );
-
-            if ret == 0 {
-                return Err(NetplanErrorDomains::NetplanFileError(
-                    "update_yaml_hierarchy failed".to_string(),
-                ));
-            }
+                &mut netplan_error,
+            )
+        };
+
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "update_yaml_hierarchy",
+            ));
         }
 
         Ok(())
     }
 
+    pub fn topology(&self) -> NetplanResult<Topology> {
+        Topology::from_netdefs(self.iter())
+    }
+
+    /// An owned copy of the netdef `id`.
+    pub fn get(&self, id: &str) -> NetplanResult<Option<Netdef>> {
+        self.raw_netdef(id).map(Netdef::from_raw_netdef).transpose()
+    }
+
+    /// A handle to the netdef `id`, borrowing the State.
+    pub fn get_ref(&self, id: &str) -> Option<NetdefRef<'_>> {
+        self.raw_netdef(id).map(NetdefRef::from_raw_netdef)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.raw_netdef(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { netplan_state_get_netdefs_size(self.state) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The IDs of all the netdefs, in the order libnetplan keeps them.
+    pub fn ids(&self) -> NetplanResult<Vec<String>> {
+        self.iter().map(|netdef| netdef.id()).collect()
+    }
+
+    /// Iterates over all the netdefs. The iterator borrows the State, so it
+    /// can be created again as many times as needed.
+    pub fn iter(&self) -> NetdefIter<'_> {
+        NetdefIter::new(self, ::std::ptr::null())
+    }
+
+    /// The netdefs of type `netdef_type`, filtered by libnetplan.
+    pub fn iter_type(&self, netdef_type: NetdefType) -> NetdefIter<'_> {
+        match netdef_type {
+            NetdefType::None => NetdefIter::empty(),
+            _ => NetdefIter::new(self, netdef_type_raw_name(netdef_type)),
+        }
+    }
+
+    pub fn ethernets(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Ethernet)
+    }
+
+    pub fn wifis(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Wifi)
+    }
+
+    pub fn modems(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Modem)
+    }
+
+    pub fn bridges(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Bridge)
+    }
+
+    pub fn bonds(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Bond)
+    }
+
+    pub fn vlans(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Vlan)
+    }
+
+    pub fn tunnels(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Tunnel)
+    }
+
+    pub fn vrfs(&self) -> NetdefIter<'_> {
+        self.iter_type(NetdefType::Vrf)
+    }
+
+    /// The global renderer, as set by the top-level `renderer` key.
+    pub fn backend(&self) -> Backend {
+        state_get_backend(self.state)
+    }
+
+    /// The SR-IOV configuration of every PF in the State.
+    pub fn sriov_plan(&self) -> NetplanResult<SriovPlan> {
+        SriovPlan::try_from(self)
+    }
+
+    pub fn dns(&self) -> NetplanResult<Dns> {
+        Dns::from_netdefs(self.iter())
+    }
+
+    /// Matches hardware `interfaces` against the physical netdefs of the
+    /// State, reporting conflicting claims and netdefs matching nothing.
+    pub fn resolve_interfaces(
+        &self,
+        interfaces: &[InterfaceInfo],
+    ) -> NetplanResult<InterfaceResolution> {
+        InterfaceResolution::from_netdefs(self.iter(), interfaces)
+    }
+
+    pub(crate) fn raw_netdef(&self, id: &str) -> Option<*const NetplanNetDefinition> {
+        let id_cstr = CString::new(id).ok()?;
+        let netdef = unsafe { netplan_state_get_netdef(self.state, id_cstr.as_ptr()) };
+
+        if netdef.is_null() {
+            None
+        } else {
+            Some(netdef as *const NetplanNetDefinition)
+        }
+    }
+
     pub fn write_yaml_file(&self, filename: &str, root_dir: &str) -> NetplanResult<()> {
-        let filename_cstr = CString::new(filename).unwrap();
-        let rootdir_cstr = CString::new(root_dir).unwrap();
-        unsafe {
-            let mut error_message = ::std::ptr::null_mut::<NetplanError>();
-            let error = netplan_state_write_yaml_file(
+        let filename_cstr = to_cstring(filename)?;
+        let rootdir_cstr = to_cstring(root_dir)?;
+        let mut netplan_error = ::std::ptr::null_mut::<GError>();
+
+        let ret = unsafe {
+            netplan_state_write_yaml_file(
                 self.state,
                 filename_cstr.as_ptr(),
                 rootdir_cstr.as_ptr(),
-                &mut error_message,
-            );
-            if error == 0 {
-                if !error_message.is_null() {
-                    if let Some(message) = error_get_message(error_message) {
-                        return Err(NetplanErrorDomains::NetplanFileError(message));
-                    } else {
-                        return Err(NetplanErrorDomains::NetplanFileError(
-                            "load hierarchy error".to_string(),
-                        ));
-                    }
-                }
-            }
+                &mut netplan_error,
+            )
+        };
+
+        if ret == 0 {
+            return Err(NetplanError::from_raw_error(
+                netplan_error,
+                "write_yaml_file",
+            ));
         }
+
         Ok(())
     }
 }
 
-impl Iterator for State {
-    type Item = Netdef;
+/* Wraps libnetplan's netdef_pertype_iter, which is freed when the iterator
+   is dropped. A NULL iter is an iterator over nothing, used for types
+   libnetplan has no name for.
+*/
+pub struct NetdefIter<'a> {
+    iter: *mut netdef_pertype_iter,
+    _state: PhantomData<&'a State>,
+}
+
+impl<'a> NetdefIter<'a> {
+    /* A NULL def_type iterates over all the netdefs */
+    fn new(state: &'a State, def_type: *const i8) -> Self {
+        NetdefIter {
+            iter: unsafe { _netplan_state_new_netdef_pertype_iter(state.state, def_type) },
+            _state: PhantomData,
+        }
+    }
+
+    fn empty() -> Self {
+        NetdefIter {
+            iter: ::std::ptr::null_mut(),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for NetdefIter<'a> {
+    type Item = NetdefRef<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.iter == ::std::ptr::null_mut() {
-            self.iter = unsafe {
-                _netplan_state_new_netdef_pertype_iter(self.state, ::std::ptr::null_mut())
-            };
+        if self.iter.is_null() {
+            return None;
         }
 
         let netdef = unsafe { _netplan_netdef_pertype_iter_next(self.iter) };
 
         if netdef.is_null() {
-            return None;
+            None
+        } else {
+            Some(NetdefRef::from_raw_netdef(netdef))
         }
+    }
+}
 
-        let netdef = Netdef::from_raw_netdef(netdef);
-        Some(netdef)
+impl Drop for NetdefIter<'_> {
+    fn drop(&mut self) {
+        if !self.iter.is_null() {
+            unsafe { _netplan_netdef_pertype_iter_free(self.iter) };
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a State {
+    type Item = NetdefRef<'a>;
+    type IntoIter = NetdefIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
@@ -185,9 +343,9 @@ impl Drop for State {
 }
 
 impl TryFrom<Parser> for State {
-    type Error = NetplanErrorDomains;
+    type Error = NetplanError;
     fn try_from(value: Parser) -> Result<Self, Self::Error> {
-        let state = State::new();
+        let mut state = State::new();
         match state.import_parser_state(value) {
             Ok(_) => Ok(state),
             Err(err) => Err(err),
@@ -198,6 +356,8 @@ impl TryFrom<Parser> for State {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ErrorDomain;
+    use crate::keypath::KeyPath;
     use crate::netdef::NetdefType;
     use std::fs::{self, File};
     use std::io::Write;
@@ -273,12 +433,14 @@ network:
 
         let parser = create_parser(yaml);
 
-        let state = State::new();
+        let mut state = State::new();
 
         if let Err(error) = state.import_parser_state(parser) {
-            if let NetplanErrorDomains::NetplanValidationError(msg) = error {
-                assert_eq!(msg, "vrf0: VRF routes table mismatch (1000 != 2000)");
-            }
+            assert_eq!(error.domain, ErrorDomain::Validation);
+            assert_eq!(
+                error.message,
+                "vrf0: VRF routes table mismatch (1000 != 2000)"
+            );
         } else {
             assert!(false, "This test should have passed but didn't");
         }
@@ -307,7 +469,7 @@ network:
 
         let parser = create_parser(yaml);
 
-        let state = State::new();
+        let mut state = State::new();
 
         if let Err(_) = state.import_parser_state(parser) {
             assert!(false, "load parser results failed");
@@ -324,7 +486,7 @@ network:
 
         let parser = create_parser(yaml);
 
-        let state = State::new();
+        let mut state = State::new();
 
         if let Err(_) = state.import_parser_state(parser) {
             assert!(false, "load parser results failed");
@@ -338,6 +500,98 @@ network:
         );
     }
 
+    #[test]
+    fn test_dump_yaml_subtree_errors() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      dhcp4: true";
+
+        let state = State::try_from(create_parser(yaml)).unwrap();
+
+        assert!(state
+            .dump_yaml_subtree("ethernets.eth0")
+            .unwrap()
+            .contains("dhcp4: true"));
+
+        let error = state.dump_yaml_subtree("ethernets.eth\00").unwrap_err();
+        assert_eq!(error.domain, ErrorDomain::Generic);
+
+        assert!(state.write_yaml_file("\0", "/").is_err());
+        assert!(state.update_yaml_hierarchy("90-config.yaml", "\0").is_err());
+    }
+
+    #[test]
+    fn test_get_path() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      mtu: 9000
+      dhcp4: true
+      addresses: [192.168.0.1/24]";
+
+        let state = State::try_from(create_parser(yaml)).unwrap();
+
+        let eth0 = state.get_path("ethernets.eth0").unwrap();
+        assert_eq!(eth0.get("mtu"), Some(&Value::Integer(9000)));
+        assert_eq!(eth0.get("dhcp4"), Some(&Value::Bool(true)));
+        assert_eq!(
+            eth0.get("addresses"),
+            Some(&Value::Sequence(vec![Value::String(
+                "192.168.0.1/24".to_string()
+            )]))
+        );
+        assert!(state.get_path("ethernets.eth1").unwrap().is_null());
+
+        assert_eq!(
+            state.get_path_as::<u32>("ethernets.eth0.mtu").unwrap(),
+            9000
+        );
+        assert_eq!(
+            state
+                .get_path_as::<Option<u32>>("ethernets.eth1.mtu")
+                .unwrap(),
+            None
+        );
+        assert!(state.get_path_as::<u32>("ethernets.eth0.dhcp4").is_err());
+
+        #[derive(serde::Deserialize)]
+        struct Ethernet {
+            mtu: u32,
+            addresses: Vec<String>,
+        }
+        let eth0: Ethernet = state.get_path_as("network.ethernets.eth0").unwrap();
+        assert_eq!(eth0.mtu, 9000);
+        assert_eq!(eth0.addresses, vec!["192.168.0.1/24"]);
+    }
+
+    #[test]
+    fn test_get_path_dotted_id() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0: {}
+  vlans:
+    eth0.100:
+      id: 100
+      link: eth0";
+
+        let state = State::try_from(create_parser(yaml)).unwrap();
+
+        assert_eq!(
+            state.get_path_as::<u32>(r#"vlans."eth0.100".id"#).unwrap(),
+            100
+        );
+        let key_path = KeyPath::from_segments(["vlans", "eth0.100", "link"]);
+        assert_eq!(
+            state.get_path(&key_path).unwrap(),
+            Value::String("eth0".to_string())
+        );
+        assert!(state.get_path(r#"vlans."eth0.100"#).is_err());
+    }
+
     #[test]
     fn test_state_iterator() {
         let yaml = r"
@@ -361,20 +615,249 @@ network:
 
         let parser = create_parser(yaml);
 
-        let state = State::new();
+        let mut state = State::new();
         state.import_parser_state(parser).unwrap();
 
         let mut netdef_ids = Vec::new();
         let netdef_ids_expected = vec!["eth0", "eth1", "eth2"];
 
-        for netdef in state {
-            assert!(matches!(netdef.r#type, NetdefType::Ethernet));
-            netdef_ids.push(netdef.id.clone());
+        for netdef in &state {
+            assert!(matches!(netdef.r#type(), NetdefType::Ethernet));
+            netdef_ids.push(netdef.id().unwrap());
         }
 
         for expected in netdef_ids_expected {
             assert!(netdef_ids.contains(&expected.to_string()));
         }
+
+        // The State can be iterated over again
+        assert_eq!(state.iter().count(), 3);
+        assert_eq!(
+            state
+                .iter()
+                .map(|netdef| netdef.id().unwrap())
+                .collect::<Vec<_>>()
+                .len(),
+            3
+        );
+
+        // Dropping a partially consumed iterator frees it
+        let mut iter = state.iter();
+        assert!(iter.next().is_some());
+        drop(iter);
+        assert_eq!(state.ids().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_state_iterator_netdef_fields() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      match:
+        macaddress: aa:bb:cc:dd:ee:ff
+      set-name: lan0
+      macaddress: 00:11:22:33:44:55
+      critical: true
+      dhcp4: true
+      dhcp6: true
+      link-local: [ ipv4 ]
+  vlans:
+    vlan100:
+      id: 100
+      link: eth0";
+
+        let parser = create_parser(yaml);
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+
+        for netdef in &state {
+            let netdef = Netdef::try_from(netdef).unwrap();
+
+            assert!(netdef.filepath.unwrap().ends_with("10-config.yaml"));
+
+            if netdef.id == "eth0" {
+                assert_eq!(netdef.set_name, Some("lan0".to_string()));
+                assert_eq!(netdef.macaddress, Some("00:11:22:33:44:55".to_string()));
+                assert!(netdef.has_match);
+                assert!(netdef.critical);
+                assert!(netdef.dhcp4);
+                assert!(netdef.dhcp6);
+                assert!(netdef.link_local_ipv4);
+                assert!(!netdef.link_local_ipv6);
+                assert_eq!(netdef.vlan_id, None);
+            } else {
+                assert_eq!(netdef.r#type, NetdefType::Vlan);
+                assert_eq!(netdef.set_name, None);
+                assert_eq!(netdef.macaddress, None);
+                assert!(!netdef.has_match);
+                assert!(!netdef.dhcp4);
+                assert_eq!(netdef.vlan_id, Some(100));
+            }
+        }
+    }
+
+    #[test]
+    fn test_state_backend() {
+        let yaml = r"
+network:
+  renderer: NetworkManager
+  ethernets:
+    eth0:
+      renderer: networkd
+    eth1: {}";
+
+        let parser = create_parser(yaml);
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+
+        assert_eq!(state.backend(), Backend::NetworkManager);
+        assert_eq!(state.backend().name(), "NetworkManager");
+
+        for netdef in &state {
+            if netdef.id().unwrap() == "eth0" {
+                assert_eq!(netdef.backend(), Backend::Networkd);
+                assert_eq!(netdef.backend().to_string(), "networkd");
+            } else {
+                assert_eq!(netdef.backend(), Backend::NetworkManager);
+            }
+        }
+    }
+
+    #[test]
+    fn test_state_lookup() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      dhcp4: true
+    eth1: {}
+  bonds:
+    bond0:
+      interfaces: [eth1]";
+
+        let parser = create_parser(yaml);
+
+        let mut state = State::new();
+        assert!(state.is_empty());
+
+        state.import_parser_state(parser).unwrap();
+
+        assert_eq!(state.len(), 3);
+        assert!(!state.is_empty());
+        assert!(state.contains("bond0"));
+        assert!(!state.contains("eth2"));
+        assert!(!state.contains("eth\0"));
+
+        let mut ids = state.ids().unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["bond0", "eth0", "eth1"]);
+
+        let netdef = state.get("eth0").unwrap().unwrap();
+        assert_eq!(netdef.id, "eth0");
+        assert!(netdef.dhcp4);
+        assert_eq!(
+            state.get("bond0").unwrap().unwrap().r#type,
+            NetdefType::Bond
+        );
+        assert!(state.get("eth2").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_state_iter_type() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0: {}
+    eth1: {}
+    eth2: {}
+  bonds:
+    bond0:
+      interfaces: [eth1, eth2]
+  bridges:
+    br0:
+      interfaces: [bond0]
+  vlans:
+    vlan100:
+      id: 100
+      link: eth0
+    vlan200:
+      id: 200
+      link: eth0";
+
+        let parser = create_parser(yaml);
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+
+        assert_eq!(NetdefType::Bond.name(), Some("bonds".to_string()));
+
+        let mut ethernets: Vec<String> = state
+            .ethernets()
+            .map(|netdef| netdef.id().unwrap())
+            .collect();
+        ethernets.sort();
+        assert_eq!(ethernets, vec!["eth0", "eth1", "eth2"]);
+
+        let bonds: Vec<String> = state.bonds().map(|netdef| netdef.id().unwrap()).collect();
+        assert_eq!(bonds, vec!["bond0"]);
+
+        let bridges: Vec<String> = state.bridges().map(|netdef| netdef.id().unwrap()).collect();
+        assert_eq!(bridges, vec!["br0"]);
+
+        assert_eq!(state.vlans().count(), 2);
+        assert!(state
+            .iter_type(NetdefType::Vlan)
+            .all(|netdef| netdef.r#type() == NetdefType::Vlan));
+
+        assert_eq!(state.wifis().count(), 0);
+        assert_eq!(state.iter_type(NetdefType::None).count(), 0);
+    }
+
+    #[test]
+    fn test_netdef_ref_links() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0:
+      dhcp4: true
+    eth1: {}
+  bonds:
+    bond0:
+      interfaces: [eth1]
+  bridges:
+    br0:
+      interfaces: [bond0]
+  vlans:
+    vlan100:
+      id: 100
+      link: eth0";
+
+        let parser = create_parser(yaml);
+
+        let mut state = State::new();
+        state.import_parser_state(parser).unwrap();
+
+        let vlan = state.get_ref("vlan100").unwrap();
+        assert_eq!(vlan.r#type(), NetdefType::Vlan);
+        assert_eq!(vlan.vlan_id(), Some(100));
+
+        let eth0 = vlan.vlan_link().unwrap();
+        assert_eq!(eth0.id().unwrap(), "eth0");
+        assert!(eth0.dhcp4());
+        assert!(eth0.bond_link().is_none());
+
+        let eth1 = state.get_ref("eth1").unwrap();
+        let bridge = eth1
+            .bond_link()
+            .and_then(|bond| bond.bridge_link())
+            .unwrap();
+        assert_eq!(bridge.id().unwrap(), "br0");
+        assert_eq!(bridge, state.get_ref("br0").unwrap());
+
+        assert!(state.get_ref("eth2").is_none());
     }
 
     #[test]
@@ -0,0 +1,313 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::error::NetplanResult;
+use crate::netdef::NetdefRef;
+
+/// The netplan setting that links one netdef to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkType {
+    /// The netdef is a port of the bridge it links to.
+    Bridge,
+    /// The netdef is a member of the bond it links to.
+    Bond,
+    /// The netdef is the peer of a veth pair or OVS patch port.
+    Peer,
+    /// The netdef is a VLAN on top of the interface it links to.
+    Vlan,
+    /// The netdef is a virtual function of the PF it links to.
+    Sriov,
+    /// The netdef is enslaved to the VRF it links to.
+    Vrf,
+}
+
+impl LinkType {
+    pub const ALL: [LinkType; 6] = [
+        LinkType::Bridge,
+        LinkType::Bond,
+        LinkType::Peer,
+        LinkType::Vlan,
+        LinkType::Sriov,
+        LinkType::Vrf,
+    ];
+}
+
+/// A typed edge between two netdefs: `from` has a `type` link pointing to `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub from: String,
+    pub to: String,
+    pub r#type: LinkType,
+}
+
+impl Link {
+    /// The interface that has to exist before the other end of the link can
+    /// be brought up, or None for peer links, which are symmetric.
+    pub fn lower(&self) -> Option<&str> {
+        match self.r#type {
+            LinkType::Bridge | LinkType::Bond | LinkType::Vrf => Some(&self.from),
+            LinkType::Vlan | LinkType::Sriov => Some(&self.to),
+            LinkType::Peer => None,
+        }
+    }
+
+    /// The interface stacked on top of the other end of the link, or None
+    /// for peer links.
+    pub fn upper(&self) -> Option<&str> {
+        match self.r#type {
+            LinkType::Bridge | LinkType::Bond | LinkType::Vrf => Some(&self.to),
+            LinkType::Vlan | LinkType::Sriov => Some(&self.from),
+            LinkType::Peer => None,
+        }
+    }
+}
+
+/// The graph of links between all the netdefs of a State.
+#[derive(Debug, Clone)]
+pub struct Topology {
+    netdefs: Vec<String>,
+    links: Vec<Link>,
+}
+
+impl Topology {
+    pub(crate) fn from_netdefs<'a>(
+        netdefs_iter: impl Iterator<Item = NetdefRef<'a>>,
+    ) -> NetplanResult<Self> {
+        let mut netdefs = Vec::new();
+        let mut links = Vec::new();
+
+        for netdef in netdefs_iter {
+            let id = netdef.id()?;
+
+            for link_type in LinkType::ALL {
+                if let Some(link) = netdef.link(link_type) {
+                    links.push(Link {
+                        from: id.clone(),
+                        to: link.id()?,
+                        r#type: link_type,
+                    });
+                }
+            }
+
+            netdefs.push(id);
+        }
+
+        Ok(Topology { netdefs, links })
+    }
+
+    /// The IDs of all the netdefs in the graph, in State order.
+    pub fn netdefs(&self) -> &[String] {
+        &self.netdefs
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Links going out of `id`, of any type, e.g. the bond `id` is a member
+    /// of as well as the interface the VLAN `id` rides on. See
+    /// lower_devices() and upper_devices() to tell them apart.
+    pub fn outgoing_links<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a Link> + 'a {
+        self.links.iter().filter(move |link| link.from == id)
+    }
+
+    /// Links pointing at `id`, of any type, e.g. the members of bond `id`
+    /// as well as the VLANs riding on it.
+    pub fn incoming_links<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a Link> + 'a {
+        self.links.iter().filter(move |link| link.to == id)
+    }
+
+    /// The IDs of the interfaces `id` sits directly on top of, e.g. the
+    /// members of bond `id` or the interface the VLAN `id` rides on.
+    pub fn lower_devices<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.links
+            .iter()
+            .filter(move |link| link.upper() == Some(id))
+            .filter_map(Link::lower)
+    }
+
+    /// The IDs of the interfaces stacked directly on top of `id`, e.g. the
+    /// bond `id` is a member of or the VLANs riding on it.
+    pub fn upper_devices<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.links
+            .iter()
+            .filter(move |link| link.lower() == Some(id))
+            .filter_map(Link::upper)
+    }
+
+    /// The IDs of every interface stacked, directly or not, on top of `id`.
+    /// These are the interfaces affected by a change to `id`.
+    pub fn blast_radius(&self, id: &str) -> Vec<String> {
+        let mut affected: Vec<String> = Vec::new();
+        let mut queue = VecDeque::from([id.to_string()]);
+
+        while let Some(current) = queue.pop_front() {
+            for link in &self.links {
+                if link.lower() != Some(current.as_str()) {
+                    continue;
+                }
+
+                if let Some(upper) = link.upper() {
+                    if upper != id && !affected.iter().any(|a| a == upper) {
+                        affected.push(upper.to_string());
+                        queue.push_back(upper.to_string());
+                    }
+                }
+            }
+        }
+
+        affected
+    }
+
+    /// All the netdef IDs sorted so that lower interfaces come before the
+    /// interfaces stacked on top of them. Ties are broken by State order.
+    /// Interfaces caught in a dependency cycle are appended at the end.
+    pub fn bring_up_order(&self) -> Vec<String> {
+        let index: HashMap<&str, usize> = self
+            .netdefs
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.as_str(), i))
+            .collect();
+
+        let mut in_degree = vec![0; self.netdefs.len()];
+        let mut uppers: Vec<Vec<usize>> = vec![Vec::new(); self.netdefs.len()];
+
+        for link in &self.links {
+            if let (Some(lower), Some(upper)) = (link.lower(), link.upper()) {
+                if let (Some(&lower), Some(&upper)) = (index.get(lower), index.get(upper)) {
+                    uppers[lower].push(upper);
+                    in_degree[upper] += 1;
+                }
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..self.netdefs.len())
+            .filter(|i| in_degree[*i] == 0)
+            .collect();
+        let mut visited = vec![false; self.netdefs.len()];
+        let mut order = Vec::new();
+
+        while let Some(current) = ready.pop_first() {
+            visited[current] = true;
+            order.push(self.netdefs[current].clone());
+
+            for upper in &uppers[current] {
+                in_degree[*upper] -= 1;
+                if in_degree[*upper] == 0 {
+                    ready.insert(*upper);
+                }
+            }
+        }
+
+        for (i, id) in self.netdefs.iter().enumerate() {
+            if !visited[i] {
+                order.push(id.clone());
+            }
+        }
+
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::state::State;
+
+    fn create_state(yaml: &str) -> State {
+        let mut parser = Parser::new();
+        parser.load_yaml_from_string(yaml).unwrap();
+
+        State::try_from(parser).unwrap()
+    }
+
+    #[test]
+    fn test_topology_links() {
+        let yaml = r"
+network:
+  ethernets:
+    eth0: {}
+    eth1: {}
+  bonds:
+    bond0:
+      interfaces: [eth0, eth1]
+  bridges:
+    br0:
+      interfaces: [bond0]
+  vlans:
+    vlan100:
+      id: 100
+      link: bond0";
+
+        let state = create_state(yaml);
+        let topology = state.topology().unwrap();
+
+        assert_eq!(topology.links().len(), 4);
+
+        let bond0_outgoing: Vec<&Link> = topology.outgoing_links("bond0").collect();
+        assert_eq!(bond0_outgoing.len(), 1);
+        assert_eq!(bond0_outgoing[0].to, "br0");
+        assert_eq!(bond0_outgoing[0].r#type, LinkType::Bridge);
+
+        let mut bond0_incoming: Vec<(&str, LinkType)> = topology
+            .incoming_links("bond0")
+            .map(|link| (link.from.as_str(), link.r#type))
+            .collect();
+        bond0_incoming.sort_by_key(|(id, _)| id.to_string());
+        assert_eq!(
+            bond0_incoming,
+            vec![
+                ("eth0", LinkType::Bond),
+                ("eth1", LinkType::Bond),
+                ("vlan100", LinkType::Vlan)
+            ]
+        );
+
+        let mut lower: Vec<&str> = topology.lower_devices("bond0").collect();
+        lower.sort();
+        assert_eq!(lower, vec!["eth0", "eth1"]);
+
+        let mut upper: Vec<&str> = topology.upper_devices("bond0").collect();
+        upper.sort();
+        assert_eq!(upper, vec!["br0", "vlan100"]);
+        assert_eq!(
+            topology.lower_devices("vlan100").collect::<Vec<_>>(),
+            vec!["bond0"]
+        );
+
+        let mut blast_radius = topology.blast_radius("eth0");
+        blast_radius.sort();
+        assert_eq!(blast_radius, vec!["bond0", "br0", "vlan100"]);
+
+        assert!(topology.blast_radius("br0").is_empty());
+    }
+
+    #[test]
+    fn test_topology_bring_up_order() {
+        let yaml = r"
+network:
+  vlans:
+    vlan100:
+      id: 100
+      link: bond0
+  bridges:
+    br0:
+      interfaces: [bond0]
+  bonds:
+    bond0:
+      interfaces: [eth0]
+  ethernets:
+    eth0: {}";
+
+        let state = create_state(yaml);
+        let order = state.topology().unwrap().bring_up_order();
+
+        let position = |id: &str| order.iter().position(|i| i == id).unwrap();
+
+        assert_eq!(order.len(), 4);
+        assert!(position("eth0") < position("bond0"));
+        assert!(position("bond0") < position("br0"));
+        assert!(position("bond0") < position("vlan100"));
+    }
+}
@@ -1,38 +1,68 @@
-use std::{
-    ffi::CString,
-    fs::File,
-    io::{Read, Seek},
-    os::fd::AsRawFd,
-    ptr::null_mut,
-};
+use std::{fs::File, os::fd::AsRawFd};
 
-use crate::libnetplan::{netplan_memfd_create, netplan_util_create_yaml_patch};
+use crate::error::NetplanError;
+use crate::error::NetplanResult;
+use crate::keypath::{KeyPath, ToKeyPath};
+use crate::libnetplan::to_cstring;
+use crate::libnetplan::GError;
+use crate::libnetplan::{netplan_memfd_create, netplan_memfd_read, netplan_util_create_yaml_patch};
+use crate::libnetplan::{netplan_memfd_from_bytes, netplan_util_dump_yaml_subtree};
 
-pub fn netplan_create_yaml_patch(conf_obj_path: &str, obj_payload: &str) -> Result<String, String> {
-    let output = netplan_memfd_create().unwrap();
+pub fn netplan_create_yaml_patch(
+    conf_obj_path: impl ToKeyPath,
+    obj_payload: &str,
+) -> NetplanResult<String> {
+    let mut output = File::from(netplan_memfd_create()?);
 
-    let patch = conf_obj_path.split(".").collect::<Vec<&str>>().join("\t");
-    let patch_cstr = CString::new(patch).unwrap();
-    let obj_payload_cstr = CString::new(obj_payload).unwrap();
+    let patch_cstr = to_cstring(&conf_obj_path.to_key_path()?.to_netplan_path()?)?;
+    let obj_payload_cstr = to_cstring(obj_payload)?;
+    let mut netplan_error = ::std::ptr::null_mut::<GError>();
 
-    unsafe {
-        let res = netplan_util_create_yaml_patch(
+    let res = unsafe {
+        netplan_util_create_yaml_patch(
             patch_cstr.as_ptr(),
             obj_payload_cstr.as_ptr(),
             output.as_raw_fd(),
-            null_mut(),
-        );
-        if res == 0 {
-            return Err("create_yaml_patch failed".to_string());
-        }
+            &mut netplan_error,
+        )
+    };
+
+    if res == 0 {
+        return Err(NetplanError::from_raw_error(
+            netplan_error,
+            "create_yaml_patch",
+        ));
     }
 
-    let mut file = File::from(output);
-    let _ = file.rewind();
-    let mut yaml_patch = String::new();
-    let _ = file.read_to_string(&mut yaml_patch);
+    netplan_memfd_read(&mut output)
+}
+
+/* The subtree of the YAML document `yaml` at `key_path`, read the way
+   libnetplan reads its configuration. It's "null" if nothing is set there.
+*/
+pub(crate) fn netplan_dump_yaml_subtree(key_path: &KeyPath, yaml: &[u8]) -> NetplanResult<String> {
+    let subtree_cstr = to_cstring(&key_path.to_netplan_path()?)?;
+    let input = netplan_memfd_from_bytes(yaml)?;
+    let mut output = File::from(netplan_memfd_create()?);
+    let mut netplan_error = ::std::ptr::null_mut::<GError>();
+
+    let res = unsafe {
+        netplan_util_dump_yaml_subtree(
+            subtree_cstr.as_ptr(),
+            input.as_raw_fd(),
+            output.as_raw_fd(),
+            &mut netplan_error,
+        )
+    };
+
+    if res == 0 {
+        return Err(NetplanError::from_raw_error(
+            netplan_error,
+            "dump_yaml_subtree",
+        ));
+    }
 
-    Ok(yaml_patch)
+    netplan_memfd_read(&mut output)
 }
 
 #[cfg(test)]
@@ -0,0 +1,427 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::error::{NetplanError, NetplanResult};
+use crate::hierarchy::VirtualHierarchy;
+use crate::keypath::KeyPath;
+use crate::netdef::NetdefType;
+use crate::parser::{hierarchy_files, Parser};
+use crate::state::State;
+use crate::utils::{netplan_create_yaml_patch, netplan_dump_yaml_subtree};
+
+/* Every netdef type libnetplan has a YAML section for */
+const NETDEF_TYPES: [NetdefType; 11] = [
+    NetdefType::Ethernet,
+    NetdefType::Wifi,
+    NetdefType::Modem,
+    NetdefType::Bridge,
+    NetdefType::Bond,
+    NetdefType::Vlan,
+    NetdefType::Tunnel,
+    NetdefType::Vrf,
+    NetdefType::Nm,
+    NetdefType::Dummy,
+    NetdefType::Veth,
+];
+
+/* Something loaded into a Validator, kept so it can be replayed */
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Yaml(PathBuf),
+    YamlBytes(Vec<u8>),
+    VirtualYaml { path: PathBuf, yaml: Vec<u8> },
+    Keyfile(PathBuf),
+}
+
+impl Source {
+    fn load(&self, parser: &mut Parser) -> NetplanResult<()> {
+        match self {
+            Source::Yaml(filename) => parser.load_yaml(filename),
+            Source::YamlBytes(yaml) => parser.load_yaml_from_bytes(yaml),
+            Source::VirtualYaml { path, yaml } => parser.load_virtual_yaml(path, yaml),
+            Source::Keyfile(filename) => parser.load_keyfile(filename),
+        }
+    }
+
+    /* The file the source stands for, if any, and its YAML */
+    fn yaml(&self) -> Option<(Option<&Path>, Vec<u8>)> {
+        match self {
+            Source::Yaml(filename) => Some((Some(filename), fs::read(filename).ok()?)),
+            Source::YamlBytes(yaml) => Some((None, yaml.clone())),
+            Source::VirtualYaml { path, yaml } => Some((Some(path), yaml.clone())),
+            Source::Keyfile(_) => None,
+        }
+    }
+}
+
+/// Collects the configuration to check, the same way a Parser loads it,
+/// and returns every problem found, not only the first one libnetplan
+/// reports.
+///
+/// The sources are replayed into new Parsers: a source that fails to load
+/// is reported and left out, and a netdef that fails validation is
+/// reported and nulled, until what's left is a valid configuration. When
+/// an error can't be tied to a netdef, each source is validated on its own
+/// instead, and if that doesn't reproduce the error, a warning says more
+/// problems may be hidden.
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    sources: Vec<Source>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    pub fn load_yaml(&mut self, filename: impl AsRef<Path>) {
+        self.sources
+            .push(Source::Yaml(filename.as_ref().to_path_buf()));
+    }
+
+    pub fn load_yaml_from_string(&mut self, yaml: &str) {
+        self.load_yaml_from_bytes(yaml.as_bytes());
+    }
+
+    pub fn load_yaml_from_bytes(&mut self, yaml: &[u8]) {
+        self.sources.push(Source::YamlBytes(yaml.to_vec()));
+    }
+
+    /// See Parser::load_virtual_hierarchy().
+    pub fn load_virtual_hierarchy(&mut self, hierarchy: &VirtualHierarchy) {
+        for file in hierarchy.files() {
+            self.sources.push(Source::VirtualYaml {
+                path: file.path(),
+                yaml: file.yaml.to_vec(),
+            });
+        }
+    }
+
+    pub fn load_keyfile(&mut self, filename: impl AsRef<Path>) {
+        self.sources
+            .push(Source::Keyfile(filename.as_ref().to_path_buf()));
+    }
+
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate_sources(&self.sources)
+    }
+}
+
+/// Validates a Parser the caller has already loaded, consuming it as
+/// State::try_from() does. A Parser doesn't keep what was loaded into it,
+/// so unlike a Validator it can't be replayed: only the first problem
+/// libnetplan reports is returned, followed by a warning that more
+/// problems may be hidden. Load the same inputs into a Validator to get
+/// every problem.
+pub fn validate_parser(parser: Parser) -> Vec<Diagnostic> {
+    match State::new().import_parser_state(parser) {
+        Ok(()) => Vec::new(),
+        Err(error) => vec![Diagnostic::from(&error), incomplete_diagnostic()],
+    }
+}
+
+/// Same as Validator::validate(), for the YAML files of the hierarchy at
+/// `root_dir`. Configuration files readable by other users are reported as
+/// warnings, as netplan does.
+pub fn validate_hierarchy(root_dir: impl AsRef<Path>) -> Vec<Diagnostic> {
+    let root_dir = root_dir.as_ref();
+    let files = match hierarchy_files(root_dir) {
+        Ok(files) => files,
+        Err(e) => {
+            let error = NetplanError::io(&root_dir.display().to_string(), &e);
+            return vec![Diagnostic::from(&error)];
+        }
+    };
+
+    let mut diagnostics: Vec<Diagnostic> = files
+        .iter()
+        .filter(|file| {
+            fs::metadata(file)
+                .map(|metadata| metadata.permissions().mode() & 0o077 != 0)
+                .unwrap_or(false)
+        })
+        .map(|file| {
+            let mut diagnostic = Diagnostic::new(
+                Severity::Warning,
+                "permissions are too open, netplan configuration should NOT be accessible by others",
+            )
+            .with_hint("chmod 600 the file");
+            diagnostic.file = Some(file.clone());
+            diagnostic
+        })
+        .collect();
+
+    let sources: Vec<Source> = files.into_iter().map(Source::Yaml).collect();
+
+    diagnostics.extend(validate_sources(&sources));
+
+    diagnostics
+}
+
+/* A netdef that failed validation: the file it's defined in, as far as
+   libnetplan is concerned, and the section it lives in.
+*/
+#[derive(Clone)]
+struct FailedNetdef {
+    id: String,
+    file: Option<PathBuf>,
+    section: String,
+}
+
+fn validate_sources(sources: &[Source]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    replay_sources(sources, Vec::new(), &mut diagnostics);
+    diagnostics
+}
+
+fn replay_sources(
+    sources: &[Source],
+    mut nulled_netdefs: Vec<FailedNetdef>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut failed_sources: Vec<usize> = Vec::new();
+
+    'replay: loop {
+        let mut parser = Parser::new();
+
+        for netdef in &nulled_netdefs {
+            let key_path = KeyPath::from_segments(["network", &netdef.section, &netdef.id]);
+            if let Ok(patch) = netplan_create_yaml_patch(key_path, "null") {
+                _ = parser.load_nullable_fields(&patch);
+            }
+        }
+
+        for (index, source) in sources.iter().enumerate() {
+            if failed_sources.contains(&index) {
+                continue;
+            }
+
+            if let Err(error) = source.load(&mut parser) {
+                push_diagnostic(diagnostics, Diagnostic::from(&error));
+                failed_sources.push(index);
+                continue 'replay;
+            }
+        }
+
+        let mut diagnostic = match State::new().import_parser_state(parser) {
+            Ok(()) => break,
+            Err(error) => Diagnostic::from(&error),
+        };
+        let netdef = failed_netdef(&diagnostic, sources, &failed_sources);
+
+        if diagnostic.file.is_none() {
+            diagnostic.file = netdef.as_ref().and_then(|netdef| netdef.file.clone());
+        }
+        let message = diagnostic.message.clone();
+        push_diagnostic(diagnostics, diagnostic);
+
+        match netdef {
+            Some(netdef) if !nulled_netdefs.iter().any(|n| n.id == netdef.id) => {
+                nulled_netdefs.push(netdef)
+            }
+            _ => {
+                /* No netdef can be left out to get past the error, so the
+                   sources are validated on their own instead
+                */
+                let loaded: Vec<&Source> = sources
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !failed_sources.contains(index))
+                    .map(|(_, source)| source)
+                    .collect();
+                let mut attributed = false;
+
+                if loaded.len() > 1 {
+                    for source in loaded {
+                        let mut source_diagnostics = Vec::new();
+                        replay_sources(
+                            std::slice::from_ref(source),
+                            nulled_netdefs.clone(),
+                            &mut source_diagnostics,
+                        );
+
+                        for source_diagnostic in source_diagnostics {
+                            attributed |= source_diagnostic.message == message;
+                            push_diagnostic(diagnostics, source_diagnostic);
+                        }
+                    }
+                }
+
+                if !attributed {
+                    push_diagnostic(diagnostics, incomplete_diagnostic());
+                }
+                break;
+            }
+        }
+    }
+}
+
+/* Added when an error hides the ones after it */
+fn incomplete_diagnostic() -> Diagnostic {
+    Diagnostic::new(
+        Severity::Warning,
+        "validation stopped at an error it couldn't get past, more problems may be hidden",
+    )
+}
+
+fn push_diagnostic(diagnostics: &mut Vec<Diagnostic>, diagnostic: Diagnostic) {
+    if !diagnostics.contains(&diagnostic) {
+        diagnostics.push(diagnostic);
+    }
+}
+
+/* libnetplan's validation errors start with the ID of the faulty netdef,
+   "id: message", but don't say where it's defined. The candidate ID is
+   only trusted if libnetplan finds that netdef in one of the sources, and
+   the last source defining it is the file libnetplan records for it.
+*/
+fn failed_netdef(
+    diagnostic: &Diagnostic,
+    sources: &[Source],
+    failed_sources: &[usize],
+) -> Option<FailedNetdef> {
+    let (id, _) = diagnostic.message.split_once(": ")?;
+
+    if id.is_empty() || id.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let sections: Vec<String> = NETDEF_TYPES
+        .iter()
+        .filter_map(|netdef_type| netdef_type.name())
+        .collect();
+
+    for (index, source) in sources.iter().enumerate().rev() {
+        if failed_sources.contains(&index) {
+            continue;
+        }
+
+        let Some((file, yaml)) = source.yaml() else {
+            continue;
+        };
+
+        for section in &sections {
+            let key_path = KeyPath::from_segments(["network", section, id]);
+
+            match netplan_dump_yaml_subtree(&key_path, &yaml) {
+                Ok(subtree) if !matches!(subtree.trim(), "" | "null") => {
+                    return Some(FailedNetdef {
+                        id: id.to_string(),
+                        file: file.map(Path::to_path_buf),
+                        section: section.clone(),
+                    });
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{DirBuilder, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_file(path: &Path, contents: &str, mode: u32) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.set_permissions(fs::Permissions::from_mode(mode))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_validate_hierarchy() {
+        let root_dir = tempdir().unwrap();
+        let etc_netplan = root_dir.path().join("etc/netplan");
+        DirBuilder::new()
+            .recursive(true)
+            .create(&etc_netplan)
+            .unwrap();
+
+        write_file(
+            &etc_netplan.join("10-bad-bool.yaml"),
+            "network:\n  ethernets:\n    eth0:\n      dhcp4: badvalue\n",
+            0o600,
+        );
+        write_file(
+            &etc_netplan.join("20-vrf.yaml"),
+            r"network:
+  ethernets:
+    eth1: {}
+  vrfs:
+    vrf0:
+      table: 1000
+      interfaces: [eth1]
+      routes:
+        - to: 192.168.0.0/24
+          via: 1.2.3.4
+          table: 2000
+",
+            0o644,
+        );
+
+        let diagnostics = validate_hierarchy(root_dir.path());
+
+        let warnings: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Warning)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].file, Some(etc_netplan.join("20-vrf.yaml")));
+
+        let errors: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 2);
+
+        assert_eq!(errors[0].file, Some(etc_netplan.join("10-bad-bool.yaml")));
+        assert_eq!(errors[0].line, Some(4));
+
+        assert!(errors[1]
+            .message
+            .starts_with("vrf0: VRF routes table mismatch"));
+        assert_eq!(errors[1].file, Some(etc_netplan.join("20-vrf.yaml")));
+    }
+
+    #[test]
+    fn test_validator() {
+        let mut validator = Validator::new();
+        validator.load_yaml_from_string("network:\n  ethernets:\n    eth0:\n      dhcp4: yes\n");
+        assert!(validator.validate().is_empty());
+
+        validator.load_yaml_from_string("network:\n  ethernets:\n    eth1:\n      dhcp6: nope\n");
+        let diagnostics = validator.validate();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .message
+            .contains("invalid boolean value 'nope'"));
+    }
+
+    #[test]
+    fn test_validate_parser() {
+        let mut parser = Parser::new();
+        parser
+            .load_yaml_from_string("network:\n  ethernets:\n    eth0:\n      dhcp4: yes\n")
+            .unwrap();
+        assert!(validate_parser(parser).is_empty());
+
+        let mut parser = Parser::new();
+        parser
+            .load_yaml_from_string("network:\n  vlans:\n    vlan10:\n      id: 10\n")
+            .unwrap();
+        let diagnostics = validate_parser(parser);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.starts_with("vlan10: "));
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+    }
+}
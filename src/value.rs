@@ -0,0 +1,210 @@
+use std::fmt;
+
+/// A node of a netplan configuration, as returned by State::get_path().
+/// Mappings keep the order netplan emits their keys in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    /// An integer above i64::MAX.
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Sequence(Vec<Value>),
+    Mapping(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            Value::UInt(value) => i64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Integer(value) => u64::try_from(*value).ok(),
+            Value::UInt(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(value) => Some(*value as f64),
+            Value::UInt(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_sequence(&self) -> Option<&[Value]> {
+        match self {
+            Value::Sequence(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_mapping(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Mapping(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// The value of `key` if this is a mapping.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_mapping()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/* Keys netplan emits are always strings, other scalars are stringified */
+fn key_to_string(key: &serde_yaml::Value) -> String {
+    match key {
+        serde_yaml::Value::String(key) => key.clone(),
+        serde_yaml::Value::Bool(key) => key.to_string(),
+        serde_yaml::Value::Number(key) => key.to_string(),
+        serde_yaml::Value::Null => "null".to_string(),
+        key => serde_yaml::to_string(key)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+    }
+}
+
+impl From<serde_yaml::Value> for Value {
+    fn from(value: serde_yaml::Value) -> Self {
+        match value {
+            serde_yaml::Value::Null => Value::Null,
+            serde_yaml::Value::Bool(value) => Value::Bool(value),
+            serde_yaml::Value::Number(number) => {
+                if let Some(value) = number.as_i64() {
+                    Value::Integer(value)
+                } else if let Some(value) = number.as_u64() {
+                    Value::UInt(value)
+                } else {
+                    Value::Float(number.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            serde_yaml::Value::String(value) => Value::String(value),
+            serde_yaml::Value::Sequence(values) => {
+                Value::Sequence(values.into_iter().map(Value::from).collect())
+            }
+            serde_yaml::Value::Mapping(entries) => Value::Mapping(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key_to_string(&key), Value::from(value)))
+                    .collect(),
+            ),
+            serde_yaml::Value::Tagged(tagged) => Value::from(tagged.value),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Integer(value) => write!(f, "{value}"),
+            Value::UInt(value) => write!(f, "{value}"),
+            Value::Float(value) => write!(f, "{value}"),
+            Value::String(value) => write!(f, "{value}"),
+            Value::Sequence(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Mapping(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_from_yaml() {
+        let yaml: serde_yaml::Value = serde_yaml::from_str(
+            "mtu: 9000\ndhcp4: true\nmacaddress: \"00:11:22:33:44:55\"\naddresses: [10.0.0.1/24]\nlink: ~\n",
+        )
+        .unwrap();
+        let value = Value::from(yaml);
+
+        let keys: Vec<&str> = value
+            .as_mapping()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["mtu", "dhcp4", "macaddress", "addresses", "link"]
+        );
+
+        assert_eq!(value.get("mtu").and_then(Value::as_i64), Some(9000));
+        assert_eq!(value.get("dhcp4").and_then(Value::as_bool), Some(true));
+        assert_eq!(
+            value.get("macaddress").and_then(Value::as_str),
+            Some("00:11:22:33:44:55")
+        );
+        assert!(value.get("link").unwrap().is_null());
+        assert_eq!(value.get("addresses").unwrap().to_string(), "[10.0.0.1/24]");
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn test_value_from_yaml_numbers() {
+        let yaml: serde_yaml::Value =
+            serde_yaml::from_str("[-1, 18446744073709551615, 1.5]").unwrap();
+        let value = Value::from(yaml);
+        let numbers = value.as_sequence().unwrap();
+
+        assert_eq!(numbers[0], Value::Integer(-1));
+        assert_eq!(numbers[0].as_u64(), None);
+        assert_eq!(numbers[1], Value::UInt(u64::MAX));
+        assert_eq!(numbers[1].as_u64(), Some(u64::MAX));
+        assert_eq!(numbers[1].as_i64(), None);
+        assert_eq!(numbers[1].to_string(), "18446744073709551615");
+        assert_eq!(numbers[2], Value::Float(1.5));
+    }
+}